@@ -0,0 +1,11 @@
+CREATE TABLE IF NOT EXISTS users (
+    id INTEGER NOT NULL AUTO_INCREMENT PRIMARY KEY,
+    firstname VARCHAR(255) NOT NULL DEFAULT '',
+    email VARCHAR(255) NOT NULL,
+    password VARCHAR(255) NOT NULL,
+    registration_code VARCHAR(255),
+    reset_code VARCHAR(255),
+    image MEDIUMBLOB,
+    create_date BIGINT UNSIGNED NOT NULL,
+    edit_date BIGINT UNSIGNED NOT NULL
+);
@@ -0,0 +1,9 @@
+CREATE TABLE refresh_tokens (
+    id INTEGER NOT NULL AUTO_INCREMENT PRIMARY KEY,
+    user_id INTEGER NOT NULL,
+    token VARCHAR(255) NOT NULL,
+    expires_at BIGINT UNSIGNED NOT NULL,
+    revoked BOOLEAN NOT NULL DEFAULT FALSE,
+    UNIQUE INDEX refresh_tokens_token (token),
+    INDEX refresh_tokens_user_id (user_id)
+);
@@ -0,0 +1,7 @@
+CREATE TABLE login_attempts (
+    id INTEGER NOT NULL AUTO_INCREMENT PRIMARY KEY,
+    email VARCHAR(255) NOT NULL,
+    ip VARCHAR(45),
+    attempted_at BIGINT UNSIGNED NOT NULL,
+    INDEX login_attempts_email_attempted_at (email, attempted_at)
+);
@@ -0,0 +1 @@
+ALTER TABLE users ADD COLUMN reset_code_expires_at BIGINT UNSIGNED NULL;
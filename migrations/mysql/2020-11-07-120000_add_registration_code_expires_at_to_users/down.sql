@@ -0,0 +1 @@
+ALTER TABLE users DROP COLUMN registration_code_expires_at;
@@ -0,0 +1,6 @@
+CREATE TABLE revoked_tokens (
+    jti VARCHAR(36) NOT NULL PRIMARY KEY,
+    revoked_at BIGINT UNSIGNED NOT NULL,
+    expires_at BIGINT UNSIGNED NOT NULL,
+    INDEX revoked_tokens_expires_at (expires_at)
+);
@@ -0,0 +1,12 @@
+CREATE TABLE sessions (
+    id INTEGER NOT NULL AUTO_INCREMENT PRIMARY KEY,
+    user_id INTEGER NOT NULL,
+    jti VARCHAR(36) NOT NULL,
+    user_agent VARCHAR(512),
+    ip VARCHAR(45),
+    created_at BIGINT UNSIGNED NOT NULL,
+    last_seen_at BIGINT UNSIGNED NOT NULL,
+    revoked BOOLEAN NOT NULL DEFAULT FALSE,
+    UNIQUE INDEX sessions_jti (jti),
+    INDEX sessions_user_id (user_id)
+);
@@ -0,0 +1,2 @@
+ALTER TABLE users DROP COLUMN email_confirmation_code;
+ALTER TABLE users DROP COLUMN pending_email;
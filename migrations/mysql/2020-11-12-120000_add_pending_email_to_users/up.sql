@@ -0,0 +1,2 @@
+ALTER TABLE users ADD COLUMN pending_email VARCHAR(255) NULL;
+ALTER TABLE users ADD COLUMN email_confirmation_code VARCHAR(255) NULL;
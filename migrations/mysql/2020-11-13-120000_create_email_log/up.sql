@@ -0,0 +1,10 @@
+CREATE TABLE email_log (
+    id INTEGER NOT NULL AUTO_INCREMENT PRIMARY KEY,
+    user_id INTEGER,
+    template VARCHAR(255) NOT NULL,
+    subject VARCHAR(255) NOT NULL,
+    sent_at BIGINT UNSIGNED NOT NULL,
+    success BOOLEAN NOT NULL,
+    error_message TEXT,
+    INDEX email_log_user_id (user_id)
+);
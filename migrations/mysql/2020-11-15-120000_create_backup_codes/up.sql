@@ -0,0 +1,7 @@
+CREATE TABLE backup_codes (
+    id INTEGER NOT NULL AUTO_INCREMENT PRIMARY KEY,
+    user_id INTEGER NOT NULL,
+    code_hash VARCHAR(255) NOT NULL,
+    used_at BIGINT UNSIGNED,
+    INDEX backup_codes_user_id (user_id)
+);
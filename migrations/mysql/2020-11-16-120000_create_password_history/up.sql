@@ -0,0 +1,7 @@
+CREATE TABLE password_history (
+    id INTEGER NOT NULL AUTO_INCREMENT PRIMARY KEY,
+    user_id INTEGER NOT NULL,
+    password_hash VARCHAR(255) NOT NULL,
+    created_at BIGINT UNSIGNED NOT NULL,
+    INDEX password_history_user_id (user_id)
+);
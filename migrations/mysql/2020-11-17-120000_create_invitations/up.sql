@@ -0,0 +1,9 @@
+CREATE TABLE invitations (
+    id INTEGER NOT NULL AUTO_INCREMENT PRIMARY KEY,
+    email VARCHAR(255) NOT NULL,
+    token VARCHAR(64) NOT NULL,
+    invited_by_user_id INTEGER NOT NULL,
+    expires_at BIGINT UNSIGNED NOT NULL,
+    accepted_at BIGINT UNSIGNED,
+    UNIQUE INDEX invitations_token (token)
+);
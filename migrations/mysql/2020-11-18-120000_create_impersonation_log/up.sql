@@ -0,0 +1,9 @@
+CREATE TABLE impersonation_log (
+    id INTEGER NOT NULL AUTO_INCREMENT PRIMARY KEY,
+    admin_id INTEGER NOT NULL,
+    user_id INTEGER NOT NULL,
+    jti VARCHAR(64) NOT NULL,
+    created_at BIGINT UNSIGNED NOT NULL,
+    expires_at BIGINT UNSIGNED NOT NULL,
+    INDEX impersonation_log_expires_at (expires_at)
+);
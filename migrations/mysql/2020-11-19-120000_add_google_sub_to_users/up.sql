@@ -0,0 +1,2 @@
+ALTER TABLE users ADD COLUMN google_sub VARCHAR(255) NULL;
+CREATE UNIQUE INDEX users_google_sub ON users (google_sub);
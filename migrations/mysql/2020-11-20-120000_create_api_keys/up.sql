@@ -0,0 +1,11 @@
+CREATE TABLE api_keys (
+    id INTEGER NOT NULL AUTO_INCREMENT PRIMARY KEY,
+    user_id INTEGER NOT NULL,
+    key_prefix VARCHAR(8) NOT NULL,
+    key_hash VARCHAR(255) NOT NULL,
+    name VARCHAR(255) NOT NULL,
+    last_used_at BIGINT UNSIGNED,
+    created_at BIGINT UNSIGNED NOT NULL,
+    revoked BOOLEAN NOT NULL DEFAULT FALSE,
+    INDEX api_keys_key_prefix (key_prefix)
+);
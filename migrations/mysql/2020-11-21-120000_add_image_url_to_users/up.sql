@@ -0,0 +1 @@
+ALTER TABLE users ADD COLUMN image_url VARCHAR(255) NULL;
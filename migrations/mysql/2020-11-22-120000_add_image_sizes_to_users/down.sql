@@ -0,0 +1,3 @@
+ALTER TABLE users DROP COLUMN image_full_url;
+ALTER TABLE users DROP COLUMN image_medium_url;
+ALTER TABLE users CHANGE image_thumb_url image_url VARCHAR(255) NULL;
@@ -0,0 +1,3 @@
+ALTER TABLE users CHANGE image_url image_thumb_url VARCHAR(255) NULL;
+ALTER TABLE users ADD COLUMN image_medium_url VARCHAR(255) NULL;
+ALTER TABLE users ADD COLUMN image_full_url VARCHAR(255) NULL;
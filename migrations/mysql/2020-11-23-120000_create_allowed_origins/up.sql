@@ -0,0 +1,5 @@
+CREATE TABLE allowed_origins (
+    id INTEGER NOT NULL AUTO_INCREMENT PRIMARY KEY,
+    origin VARCHAR(255) NOT NULL,
+    UNIQUE INDEX allowed_origins_origin (origin)
+);
@@ -0,0 +1,18 @@
+CREATE TABLE webhooks (
+    id INTEGER NOT NULL AUTO_INCREMENT PRIMARY KEY,
+    url VARCHAR(2048) NOT NULL,
+    secret VARCHAR(255) NOT NULL,
+    events TEXT NOT NULL,
+    created_by_user_id INTEGER NOT NULL,
+    active BOOLEAN NOT NULL DEFAULT TRUE
+);
+CREATE TABLE webhook_deliveries (
+    id INTEGER NOT NULL AUTO_INCREMENT PRIMARY KEY,
+    webhook_id INTEGER NOT NULL,
+    event VARCHAR(255) NOT NULL,
+    attempt INTEGER NOT NULL,
+    status_code INTEGER NULL,
+    error_message TEXT NULL,
+    delivered_at BIGINT NOT NULL,
+    INDEX webhook_deliveries_webhook_id (webhook_id)
+);
@@ -0,0 +1,2 @@
+ALTER TABLE users ADD COLUMN username VARCHAR(32);
+CREATE UNIQUE INDEX users_username_unique ON users (username);
@@ -0,0 +1,2 @@
+DROP TABLE phone_otps;
+ALTER TABLE users DROP COLUMN phone_verified;
@@ -0,0 +1,8 @@
+ALTER TABLE users ADD COLUMN phone_verified TINYINT(1) NOT NULL DEFAULT 0;
+CREATE TABLE phone_otps (
+    id INTEGER NOT NULL AUTO_INCREMENT PRIMARY KEY,
+    user_id INTEGER NOT NULL,
+    otp_hash VARCHAR(255) NOT NULL,
+    expires_at BIGINT UNSIGNED NOT NULL,
+    INDEX phone_otps_user_id (user_id)
+);
@@ -0,0 +1,2 @@
+ALTER TABLE users ADD COLUMN bio TEXT NULL;
+ALTER TABLE users ADD COLUMN website VARCHAR(2048) NULL;
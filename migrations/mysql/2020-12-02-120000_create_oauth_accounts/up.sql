@@ -0,0 +1,15 @@
+CREATE TABLE oauth_accounts (
+    id INTEGER NOT NULL AUTO_INCREMENT PRIMARY KEY,
+    user_id INTEGER NOT NULL,
+    provider VARCHAR(32) NOT NULL,
+    provider_sub VARCHAR(255) NOT NULL,
+    access_token_encrypted TEXT,
+    refresh_token_encrypted TEXT,
+    expires_at BIGINT UNSIGNED,
+    created_at BIGINT UNSIGNED NOT NULL,
+    UNIQUE INDEX oauth_accounts_provider_sub (provider, provider_sub),
+    INDEX oauth_accounts_user_id (user_id)
+);
+-- Google accounts that were linked before are taken over
+INSERT INTO oauth_accounts (user_id, provider, provider_sub, created_at)
+SELECT id, 'google', google_sub, UNIX_TIMESTAMP() FROM users WHERE google_sub IS NOT NULL;
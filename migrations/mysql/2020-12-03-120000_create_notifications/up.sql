@@ -0,0 +1,9 @@
+CREATE TABLE notifications (
+    id INTEGER NOT NULL AUTO_INCREMENT PRIMARY KEY,
+    user_id INTEGER NOT NULL,
+    type VARCHAR(64) NOT NULL,
+    payload TEXT NOT NULL,
+    read_at BIGINT NULL,
+    created_at BIGINT NOT NULL,
+    INDEX notifications_user_id (user_id)
+);
@@ -0,0 +1,6 @@
+CREATE TABLE email_preferences (
+    user_id INTEGER NOT NULL,
+    type VARCHAR(64) NOT NULL,
+    subscribed TINYINT(1) NOT NULL DEFAULT 1,
+    PRIMARY KEY (user_id, type)
+);
@@ -0,0 +1,5 @@
+DROP INDEX users_email_unique ON users;
+CREATE UNIQUE INDEX users_email_unique ON users (email);
+ALTER TABLE users DROP FOREIGN KEY users_tenant_id;
+ALTER TABLE users DROP COLUMN tenant_id;
+DROP TABLE tenants;
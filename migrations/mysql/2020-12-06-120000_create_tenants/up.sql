@@ -0,0 +1,12 @@
+CREATE TABLE tenants (
+    id INTEGER NOT NULL AUTO_INCREMENT PRIMARY KEY,
+    name VARCHAR(255) NOT NULL,
+    slug VARCHAR(63) NOT NULL,
+    created_at BIGINT UNSIGNED NOT NULL,
+    UNIQUE INDEX tenants_slug (slug)
+);
+ALTER TABLE users ADD COLUMN tenant_id INTEGER NULL;
+ALTER TABLE users ADD CONSTRAINT users_tenant_id FOREIGN KEY (tenant_id) REFERENCES tenants (id);
+-- the same email address can be used once per tenant
+DROP INDEX users_email_unique ON users;
+CREATE UNIQUE INDEX users_email_unique ON users (tenant_id, email);
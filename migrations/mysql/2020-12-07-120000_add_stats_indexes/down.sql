@@ -0,0 +1,2 @@
+DROP INDEX login_attempts_attempted_at ON login_attempts;
+DROP INDEX users_create_date ON users;
@@ -0,0 +1,3 @@
+-- the admin stats count users and failed logins by their time without a full table scan
+CREATE INDEX users_create_date ON users (create_date);
+CREATE INDEX login_attempts_attempted_at ON login_attempts (attempted_at);
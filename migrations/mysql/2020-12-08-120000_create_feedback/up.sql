@@ -0,0 +1,8 @@
+CREATE TABLE feedback (
+    id INTEGER NOT NULL AUTO_INCREMENT PRIMARY KEY,
+    user_id INTEGER NOT NULL,
+    category VARCHAR(16) NOT NULL,
+    message TEXT NOT NULL,
+    created_at BIGINT UNSIGNED NOT NULL,
+    INDEX feedback_user_id (user_id)
+);
@@ -0,0 +1,6 @@
+ALTER TABLE invitations DROP FOREIGN KEY invitations_tenant_id;
+ALTER TABLE invitations DROP COLUMN tenant_id;
+ALTER TABLE webhooks DROP FOREIGN KEY webhooks_tenant_id;
+ALTER TABLE webhooks DROP COLUMN tenant_id;
+ALTER TABLE login_attempts DROP FOREIGN KEY login_attempts_tenant_id;
+ALTER TABLE login_attempts DROP COLUMN tenant_id;
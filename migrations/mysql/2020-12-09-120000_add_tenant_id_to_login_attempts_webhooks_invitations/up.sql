@@ -0,0 +1,7 @@
+-- failed logins, webhooks and invitations of one tenant do not affect the other tenants
+ALTER TABLE login_attempts ADD COLUMN tenant_id INTEGER NULL;
+ALTER TABLE login_attempts ADD CONSTRAINT login_attempts_tenant_id FOREIGN KEY (tenant_id) REFERENCES tenants (id);
+ALTER TABLE webhooks ADD COLUMN tenant_id INTEGER NULL;
+ALTER TABLE webhooks ADD CONSTRAINT webhooks_tenant_id FOREIGN KEY (tenant_id) REFERENCES tenants (id);
+ALTER TABLE invitations ADD COLUMN tenant_id INTEGER NULL;
+ALTER TABLE invitations ADD CONSTRAINT invitations_tenant_id FOREIGN KEY (tenant_id) REFERENCES tenants (id);
@@ -0,0 +1,4 @@
+DROP INDEX users_email_unique ON users;
+CREATE UNIQUE INDEX users_email_unique ON users (tenant_id, email);
+DROP INDEX users_tenant_id_index ON users;
+ALTER TABLE users DROP COLUMN email_tenant_id;
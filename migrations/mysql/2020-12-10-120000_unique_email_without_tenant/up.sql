@@ -0,0 +1,6 @@
+-- NULLs are distinct in a unique index, so users without a tenant are compared with the tenant id 0 instead
+ALTER TABLE users ADD COLUMN email_tenant_id INTEGER AS (COALESCE(tenant_id, 0)) STORED NOT NULL;
+-- the foreign key of the tenant needs an index of its own once the unique index does not start with tenant_id
+CREATE INDEX users_tenant_id_index ON users (tenant_id);
+DROP INDEX users_email_unique ON users;
+CREATE UNIQUE INDEX users_email_unique ON users (email_tenant_id, email);
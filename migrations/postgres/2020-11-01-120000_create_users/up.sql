@@ -0,0 +1,11 @@
+CREATE TABLE IF NOT EXISTS users (
+    id SERIAL PRIMARY KEY,
+    firstname VARCHAR(255) NOT NULL DEFAULT '',
+    email VARCHAR(255) NOT NULL,
+    password VARCHAR(255) NOT NULL,
+    registration_code VARCHAR(255),
+    reset_code VARCHAR(255),
+    image BYTEA,
+    create_date BIGINT NOT NULL,
+    edit_date BIGINT NOT NULL
+);
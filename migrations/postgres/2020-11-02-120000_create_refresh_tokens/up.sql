@@ -0,0 +1,9 @@
+CREATE TABLE refresh_tokens (
+    id SERIAL PRIMARY KEY,
+    user_id INTEGER NOT NULL,
+    token VARCHAR(255) NOT NULL,
+    expires_at BIGINT NOT NULL,
+    revoked BOOLEAN NOT NULL DEFAULT FALSE
+);
+CREATE UNIQUE INDEX refresh_tokens_token ON refresh_tokens (token);
+CREATE INDEX refresh_tokens_user_id ON refresh_tokens (user_id);
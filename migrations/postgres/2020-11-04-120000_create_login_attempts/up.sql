@@ -0,0 +1,7 @@
+CREATE TABLE login_attempts (
+    id SERIAL PRIMARY KEY,
+    email VARCHAR(255) NOT NULL,
+    ip VARCHAR(45),
+    attempted_at BIGINT NOT NULL
+);
+CREATE INDEX login_attempts_email_attempted_at ON login_attempts (email, attempted_at);
@@ -0,0 +1 @@
+ALTER TABLE users ADD COLUMN registration_code_expires_at BIGINT NULL;
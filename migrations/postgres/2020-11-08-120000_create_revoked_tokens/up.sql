@@ -0,0 +1,6 @@
+CREATE TABLE revoked_tokens (
+    jti VARCHAR(36) NOT NULL PRIMARY KEY,
+    revoked_at BIGINT NOT NULL,
+    expires_at BIGINT NOT NULL
+);
+CREATE INDEX revoked_tokens_expires_at ON revoked_tokens (expires_at);
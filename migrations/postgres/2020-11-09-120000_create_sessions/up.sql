@@ -0,0 +1,12 @@
+CREATE TABLE sessions (
+    id SERIAL PRIMARY KEY,
+    user_id INTEGER NOT NULL,
+    jti VARCHAR(36) NOT NULL,
+    user_agent VARCHAR(512),
+    ip VARCHAR(45),
+    created_at BIGINT NOT NULL,
+    last_seen_at BIGINT NOT NULL,
+    revoked BOOLEAN NOT NULL DEFAULT FALSE
+);
+CREATE UNIQUE INDEX sessions_jti ON sessions (jti);
+CREATE INDEX sessions_user_id ON sessions (user_id);
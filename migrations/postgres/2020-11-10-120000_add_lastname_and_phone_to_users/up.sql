@@ -0,0 +1,2 @@
+ALTER TABLE users ADD COLUMN lastname VARCHAR(255) NULL;
+ALTER TABLE users ADD COLUMN phone VARCHAR(32) NULL;
@@ -0,0 +1,2 @@
+ALTER TABLE users ADD COLUMN last_login_at BIGINT NULL;
+ALTER TABLE users ADD COLUMN last_login_ip VARCHAR(45) NULL;
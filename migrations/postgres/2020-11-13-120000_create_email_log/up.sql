@@ -0,0 +1,10 @@
+CREATE TABLE email_log (
+    id SERIAL PRIMARY KEY,
+    user_id INTEGER,
+    template VARCHAR(255) NOT NULL,
+    subject VARCHAR(255) NOT NULL,
+    sent_at BIGINT NOT NULL,
+    success BOOLEAN NOT NULL,
+    error_message TEXT
+);
+CREATE INDEX email_log_user_id ON email_log (user_id);
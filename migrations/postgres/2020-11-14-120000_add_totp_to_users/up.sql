@@ -0,0 +1,2 @@
+ALTER TABLE users ADD COLUMN totp_secret VARCHAR(255) NULL;
+ALTER TABLE users ADD COLUMN totp_enabled BOOLEAN NOT NULL DEFAULT FALSE;
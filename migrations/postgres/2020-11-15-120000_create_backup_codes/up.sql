@@ -0,0 +1,7 @@
+CREATE TABLE backup_codes (
+    id SERIAL PRIMARY KEY,
+    user_id INTEGER NOT NULL,
+    code_hash VARCHAR(255) NOT NULL,
+    used_at BIGINT
+);
+CREATE INDEX backup_codes_user_id ON backup_codes (user_id);
@@ -0,0 +1,7 @@
+CREATE TABLE password_history (
+    id SERIAL PRIMARY KEY,
+    user_id INTEGER NOT NULL,
+    password_hash VARCHAR(255) NOT NULL,
+    created_at BIGINT NOT NULL
+);
+CREATE INDEX password_history_user_id ON password_history (user_id);
@@ -0,0 +1,9 @@
+CREATE TABLE invitations (
+    id SERIAL PRIMARY KEY,
+    email VARCHAR(255) NOT NULL,
+    token VARCHAR(64) NOT NULL,
+    invited_by_user_id INTEGER NOT NULL,
+    expires_at BIGINT NOT NULL,
+    accepted_at BIGINT
+);
+CREATE UNIQUE INDEX invitations_token ON invitations (token);
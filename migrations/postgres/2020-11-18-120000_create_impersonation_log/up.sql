@@ -0,0 +1,9 @@
+CREATE TABLE impersonation_log (
+    id SERIAL PRIMARY KEY,
+    admin_id INTEGER NOT NULL,
+    user_id INTEGER NOT NULL,
+    jti VARCHAR(64) NOT NULL,
+    created_at BIGINT NOT NULL,
+    expires_at BIGINT NOT NULL
+);
+CREATE INDEX impersonation_log_expires_at ON impersonation_log (expires_at);
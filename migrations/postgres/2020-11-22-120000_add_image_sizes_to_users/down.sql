@@ -0,0 +1,3 @@
+ALTER TABLE users DROP COLUMN image_full_url;
+ALTER TABLE users DROP COLUMN image_medium_url;
+ALTER TABLE users RENAME COLUMN image_thumb_url TO image_url;
@@ -0,0 +1,3 @@
+ALTER TABLE users RENAME COLUMN image_url TO image_thumb_url;
+ALTER TABLE users ADD COLUMN image_medium_url VARCHAR(255) NULL;
+ALTER TABLE users ADD COLUMN image_full_url VARCHAR(255) NULL;
@@ -0,0 +1,5 @@
+CREATE TABLE allowed_origins (
+    id SERIAL PRIMARY KEY,
+    origin VARCHAR(255) NOT NULL
+);
+CREATE UNIQUE INDEX allowed_origins_origin ON allowed_origins (origin);
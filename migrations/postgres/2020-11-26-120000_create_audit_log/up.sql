@@ -0,0 +1,11 @@
+CREATE TABLE audit_log (
+    id SERIAL PRIMARY KEY,
+    user_id INTEGER NOT NULL,
+    actor_id INTEGER NULL,
+    action VARCHAR(255) NOT NULL,
+    old_value TEXT NOT NULL,
+    new_value TEXT NOT NULL,
+    ip VARCHAR(255) NULL,
+    created_at BIGINT NOT NULL
+);
+CREATE INDEX audit_log_user_id ON audit_log (user_id);
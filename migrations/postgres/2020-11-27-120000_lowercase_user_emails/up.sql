@@ -0,0 +1,2 @@
+UPDATE users SET email = LOWER(email), pending_email = LOWER(pending_email);
+CREATE UNIQUE INDEX users_email_unique ON users (LOWER(email));
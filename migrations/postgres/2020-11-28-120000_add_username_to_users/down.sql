@@ -0,0 +1,2 @@
+DROP INDEX users_username_unique;
+ALTER TABLE users DROP COLUMN username;
@@ -0,0 +1,8 @@
+ALTER TABLE users ADD COLUMN phone_verified BOOLEAN NOT NULL DEFAULT FALSE;
+CREATE TABLE phone_otps (
+    id SERIAL PRIMARY KEY,
+    user_id INTEGER NOT NULL,
+    otp_hash VARCHAR(255) NOT NULL,
+    expires_at BIGINT NOT NULL
+);
+CREATE INDEX phone_otps_user_id ON phone_otps (user_id);
@@ -0,0 +1,9 @@
+CREATE TABLE notifications (
+    id SERIAL PRIMARY KEY,
+    user_id INTEGER NOT NULL,
+    type VARCHAR(64) NOT NULL,
+    payload TEXT NOT NULL,
+    read_at BIGINT NULL,
+    created_at BIGINT NOT NULL
+);
+CREATE INDEX notifications_user_id ON notifications (user_id);
@@ -0,0 +1,6 @@
+CREATE TABLE email_preferences (
+    user_id INTEGER NOT NULL,
+    type VARCHAR(64) NOT NULL,
+    subscribed BOOLEAN NOT NULL DEFAULT TRUE,
+    PRIMARY KEY (user_id, type)
+);
@@ -0,0 +1 @@
+ALTER TABLE users ADD COLUMN avatar_url VARCHAR(2048) NULL;
@@ -0,0 +1,4 @@
+DROP INDEX users_email_unique;
+CREATE UNIQUE INDEX users_email_unique ON users (LOWER(email));
+ALTER TABLE users DROP COLUMN tenant_id;
+DROP TABLE tenants;
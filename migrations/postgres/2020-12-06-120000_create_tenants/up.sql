@@ -0,0 +1,11 @@
+CREATE TABLE tenants (
+    id SERIAL PRIMARY KEY,
+    name VARCHAR(255) NOT NULL,
+    slug VARCHAR(63) NOT NULL,
+    created_at BIGINT NOT NULL
+);
+CREATE UNIQUE INDEX tenants_slug ON tenants (slug);
+ALTER TABLE users ADD COLUMN tenant_id INTEGER NULL REFERENCES tenants (id);
+-- the same email address can be used once per tenant
+DROP INDEX users_email_unique;
+CREATE UNIQUE INDEX users_email_unique ON users (tenant_id, LOWER(email));
@@ -0,0 +1,8 @@
+CREATE TABLE feedback (
+    id SERIAL PRIMARY KEY,
+    user_id INTEGER NOT NULL,
+    category VARCHAR(16) NOT NULL,
+    message TEXT NOT NULL,
+    created_at BIGINT NOT NULL
+);
+CREATE INDEX feedback_user_id ON feedback (user_id);
@@ -0,0 +1,3 @@
+ALTER TABLE invitations DROP COLUMN tenant_id;
+ALTER TABLE webhooks DROP COLUMN tenant_id;
+ALTER TABLE login_attempts DROP COLUMN tenant_id;
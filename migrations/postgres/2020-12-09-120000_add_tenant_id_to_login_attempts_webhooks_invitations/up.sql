@@ -0,0 +1,4 @@
+-- failed logins, webhooks and invitations of one tenant do not affect the other tenants
+ALTER TABLE login_attempts ADD COLUMN tenant_id INTEGER NULL REFERENCES tenants (id);
+ALTER TABLE webhooks ADD COLUMN tenant_id INTEGER NULL REFERENCES tenants (id);
+ALTER TABLE invitations ADD COLUMN tenant_id INTEGER NULL REFERENCES tenants (id);
@@ -0,0 +1,3 @@
+-- NULLs are distinct in a unique index, so users without a tenant are compared with the tenant id 0 instead
+DROP INDEX users_email_unique;
+CREATE UNIQUE INDEX users_email_unique ON users (COALESCE(tenant_id, 0), LOWER(email));
@@ -0,0 +1,104 @@
+//! Audit log of the changes of users. Every route that changes a user records which fields were changed, by whom
+//! and from which ip address
+use serde_json::{Map, Value};
+
+use crate::Connection;
+use crate::user::model::{AuditLog, User};
+
+/// Fields of a user that are recorded in the audit log. Secrets like the password hash or one time codes are
+/// never recorded, only whether they have changed
+fn snapshot(user: &User) -> Map<String, Value> {
+    let fields = serde_json::json!({
+        "email": user.email,
+        "username": user.username,
+        "timezone": user.timezone,
+        "locale": user.locale,
+        "firstname": user.firstname,
+        "lastname": user.lastname,
+        "phone": user.phone,
+        "phone_verified": user.phone_verified,
+        "bio": user.bio,
+        "website": user.website,
+        "avatar_url": user.avatar_url,
+        "pending_email": user.pending_email,
+        "role": user.role,
+        "is_confirmed": user.registration_code.is_none(),
+        "is_active": user.is_active,
+        "totp_enabled": user.totp_enabled,
+        "google_sub": user.google_sub,
+        "last_login_at": user.last_login_at,
+        "last_login_ip": user.last_login_ip,
+        "image_thumb_url": user.image_thumb_url,
+        "image_medium_url": user.image_medium_url,
+        "image_full_url": user.image_full_url,
+        "has_legacy_image": user.image.is_some(),
+    });
+    match fields {
+        Value::Object(fields) => fields,
+        _ => Map::new()
+    }
+}
+
+/// Names of the secret fields of a user
+const SECRET_FIELDS: [&str; 5] = ["password", "registration_code", "reset_code", "email_confirmation_code", "totp_secret"];
+
+/// Secret fields of a user. Their values are never recorded, only whether they have changed
+fn secrets(user: &User) -> [(&'static str, Option<&String>); 5] {
+    [
+        (SECRET_FIELDS[0], Some(&user.password)),
+        (SECRET_FIELDS[1], user.registration_code.as_ref()),
+        (SECRET_FIELDS[2], user.reset_code.as_ref()),
+        (SECRET_FIELDS[3], user.email_confirmation_code.as_ref()),
+        (SECRET_FIELDS[4], user.totp_secret.as_ref()),
+    ]
+}
+
+/// The changed fields of a user before and after a change
+pub fn changes(old: &User, new: &User) -> (Value, Value) {
+    let old_fields = snapshot(old);
+    let mut new_fields: Map<String, Value> = snapshot(new).into_iter().filter(|(key, value)| old_fields.get(key) != Some(value)).collect();
+    let mut old_fields: Map<String, Value> = old_fields.into_iter().filter(|(key, _)| new_fields.contains_key(key)).collect();
+    for ((key, old_secret), (_, new_secret)) in secrets(old).iter().zip(secrets(new).iter()) {
+        if old_secret != new_secret {
+            old_fields.insert(key.to_string(), Value::from(if old_secret.is_some() { "[redacted]" } else { "[none]" }));
+            new_fields.insert(key.to_string(), Value::from(if new_secret.is_some() { "[changed]" } else { "[none]" }));
+        }
+    }
+    (Value::Object(old_fields), Value::Object(new_fields))
+}
+
+/// An audit log entry with the recorded changes as JSON objects instead of strings
+pub fn to_json(entry: &AuditLog) -> Value {
+    serde_json::json!({
+        "id": entry.id,
+        "user_id": entry.user_id,
+        "actor_id": entry.actor_id,
+        "action": entry.action,
+        "old_value": serde_json::from_str::<Value>(&entry.old_value).unwrap_or(Value::Null),
+        "new_value": serde_json::from_str::<Value>(&entry.new_value).unwrap_or(Value::Null),
+        "ip": entry.ip,
+        "created_at": entry.created_at,
+    })
+}
+
+/// An audit log entry for the changed user. Only shows which secret fields have changed
+pub fn to_redacted_json(entry: &AuditLog) -> Value {
+    let mut json = to_json(entry);
+    for key in &["old_value", "new_value"] {
+        if let Some(Value::Object(fields)) = json.get_mut(*key) {
+            for secret in SECRET_FIELDS.iter().filter(|secret| fields.contains_key(**secret)) {
+                fields.insert(secret.to_string(), Value::from("[redacted]"));
+            }
+        }
+    }
+    json
+}
+
+/// Record a change of a user. `actor_id` is the user who made the change and is empty for changes by
+/// unauthenticated requests like an activation link. Errors are logged and do not fail the request
+pub fn log(connection: &Connection, user_id: i32, actor_id: Option<i32>, action: &str, old: &User, new: &User, ip: Option<String>) {
+    let (old_value, new_value) = changes(old, new);
+    if !AuditLog::create(user_id, actor_id, action, old_value.to_string(), new_value.to_string(), ip, connection) {
+        error!("[{}] Audit log entry {} for user {} could not be written", crate::request_id::current(), action, user_id);
+    }
+}
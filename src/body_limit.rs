@@ -0,0 +1,67 @@
+//! Global limit for the size of request bodies. Requests with a larger `Content-Length` are answered with
+//! `413 Payload Too Large` before a route reads the body
+use rocket::{Data, Request};
+use rocket::fairing::{Fairing, Info, Kind};
+use rocket::http::Method;
+use rocket::http::uri::Origin;
+use rocket_contrib::json::Json;
+
+use crate::{CustomResponder, ErrorCode};
+
+/// Maximum size of a request body if `http.max_body_bytes` is not configured
+const DEFAULT_MAX_BODY_BYTES: u64 = 10 * 1024 * 1024;
+/// Path of the route that answers requests with a too large body
+const PAYLOAD_TOO_LARGE_PATH: &str = "/payload_too_large";
+
+/// Attach the fairing and mount the route for requests with a too large body
+pub fn mount(rocket: rocket::Rocket) -> rocket::Rocket {
+    rocket
+        .attach(BodySizeLimit::from_config())
+        .mount("/", routes![payload_too_large])
+}
+
+/// Fairing that redirects requests whose `Content-Length` exceeds the limit to an error route, so the body is
+/// never read. Requests without `Content-Length` (chunked) are limited by the routes themselves
+pub struct BodySizeLimit {
+    max_body_bytes: u64,
+}
+
+impl BodySizeLimit {
+    /// Read `http.max_body_bytes` from the configuration file. Panics on invalid values
+    pub fn from_config() -> BodySizeLimit {
+        let configuration = crate::load_config().ok();
+        let max_body_bytes = match configuration.as_ref().and_then(|c| c.get_int("http.max_body_bytes").ok()) {
+            Some(bytes) if bytes > 0 => bytes as u64,
+            Some(bytes) => panic!("http.max_body_bytes has to be positive but is {}", bytes),
+            None => DEFAULT_MAX_BODY_BYTES
+        };
+        BodySizeLimit { max_body_bytes }
+    }
+}
+
+impl Fairing for BodySizeLimit {
+    fn info(&self) -> Info {
+        Info {
+            name: "Body size limit",
+            kind: Kind::Request,
+        }
+    }
+
+    fn on_request(&self, request: &mut Request, _data: &Data) {
+        let content_length = request.headers().get_one("Content-Length").and_then(|length| length.parse::<u64>().ok());
+        if let Some(length) = content_length {
+            if length > self.max_body_bytes {
+                warn!("[{}] Request body of {} bytes exceeds the limit of {} bytes", crate::request_id::current(), length, self.max_body_bytes);
+                // fairings can not respond themselves, so the request is routed to the error route
+                request.set_method(Method::Get);
+                request.set_uri(Origin::parse(PAYLOAD_TOO_LARGE_PATH).unwrap());
+            }
+        }
+    }
+}
+
+/// Error route for requests whose body exceeds `http.max_body_bytes`
+#[get("/payload_too_large")]
+fn payload_too_large() -> CustomResponder {
+    CustomResponder::PayloadTooLarge(Json(json!({"status": {"code": 413,"text": "Request body is too large", "error_code": ErrorCode::PayloadTooLarge}})))
+}
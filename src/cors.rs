@@ -0,0 +1,108 @@
+//! CORS with allowed origins from the `allowed_origins` table. Used instead of the static configuration of
+//! `make_cors()` if `[cors] mode = "dynamic"` is configured, so origins of new tenants are allowed without a restart
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use diesel::prelude::*;
+use rocket::{Request, Response};
+use rocket::fairing::{Fairing, Info, Kind};
+use rocket::http::{Header, Method, Status};
+
+use crate::{DbConn, CORS_ALLOWED_HEADERS, CORS_EXPOSE_HEADERS};
+use crate::user::schema::allowed_origins;
+
+/// Time for which the allowed origins are cached if `cors.cache_seconds` is not configured
+const DEFAULT_CACHE_SECONDS: u64 = 60;
+/// Methods that are allowed for cross origin requests
+const ALLOWED_METHODS: &str = "GET, POST, PUT, PATCH, DELETE";
+
+/// Whether the allowed origins are read from the database
+pub fn dynamic_mode() -> bool {
+    match crate::load_config() {
+        Ok(config) => config.get_str("cors.mode").map(|mode| mode == "dynamic").unwrap_or(false),
+        Err(_) => false
+    }
+}
+
+/// Attach the fairing and mount the route that answers preflight requests
+pub fn mount(rocket: rocket::Rocket) -> rocket::Rocket {
+    rocket
+        .attach(DynamicCorsFairing::from_config())
+        .mount("/", routes![preflight])
+}
+
+/// Fairing that sets the CORS headers for origins from the `allowed_origins` table
+pub struct DynamicCorsFairing {
+    cache_duration: Duration,
+    /// Allowed origins and the time they were loaded
+    cache: Mutex<Option<(Instant, Vec<String>)>>,
+}
+
+impl DynamicCorsFairing {
+    /// Read `cors.cache_seconds` from the configuration. 0 reads the origins on every request
+    pub fn from_config() -> DynamicCorsFairing {
+        let cache_seconds = match crate::load_config().ok().and_then(|c| c.get_int("cors.cache_seconds").ok()) {
+            Some(seconds) if seconds >= 0 => seconds as u64,
+            Some(seconds) => panic!("cors.cache_seconds must not be negative but is {}", seconds),
+            None => DEFAULT_CACHE_SECONDS
+        };
+        DynamicCorsFairing { cache_duration: Duration::from_secs(cache_seconds), cache: Mutex::new(None) }
+    }
+
+    /// Check whether the origin is allowed. The origins are loaded again when the cache is expired
+    fn is_allowed(&self, origin: &str, request: &Request) -> bool {
+        let mut cache = self.cache.lock().unwrap();
+        let expired = match &*cache {
+            Some((loaded_at, _)) => loaded_at.elapsed() >= self.cache_duration,
+            None => true
+        };
+        if expired {
+            match request.guard::<DbConn>().succeeded() {
+                Some(connection) => match allowed_origins::table.select(allowed_origins::origin).load::<String>(&connection.0) {
+                    Ok(origins) => *cache = Some((Instant::now(), origins)),
+                    // keep the previous origins if the database is not available
+                    Err(e) => error!("[{}] Allowed origins could not be loaded: {}", crate::request_id::current(), e)
+                },
+                None => error!("[{}] Allowed origins could not be loaded: no database connection", crate::request_id::current())
+            }
+        }
+        match &*cache {
+            Some((_, origins)) => origins.iter().any(|allowed| allowed == origin),
+            None => false
+        }
+    }
+}
+
+impl Fairing for DynamicCorsFairing {
+    fn info(&self) -> Info {
+        Info {
+            name: "Dynamic CORS",
+            kind: Kind::Response,
+        }
+    }
+
+    fn on_response(&self, request: &Request, response: &mut Response) {
+        let origin = match request.headers().get_one("Origin") {
+            Some(origin) => origin.to_string(),
+            None => return
+        };
+        // the response depends on the origin, so caches have to distinguish them
+        response.adjoin_header(Header::new("Vary", "Origin"));
+        if !self.is_allowed(&origin, request) {
+            return;
+        }
+        response.set_header(Header::new("Access-Control-Allow-Origin", origin));
+        response.set_header(Header::new("Access-Control-Allow-Credentials", "true"));
+        response.set_header(Header::new("Access-Control-Expose-Headers", CORS_EXPOSE_HEADERS.join(", ")));
+        if request.method() == Method::Options {
+            response.set_header(Header::new("Access-Control-Allow-Methods", ALLOWED_METHODS));
+            response.set_header(Header::new("Access-Control-Allow-Headers", CORS_ALLOWED_HEADERS.join(", ")));
+        }
+    }
+}
+
+/// Answer preflight requests of all routes. The CORS headers are set by the fairing
+#[options("/<_path..>")]
+fn preflight(_path: std::path::PathBuf) -> Status {
+    Status::NoContent
+}
@@ -0,0 +1,18 @@
+//! Mapping of errors that many routes share to the error responses of the API
+use rocket_contrib::json::{Json, JsonError};
+
+use crate::{CustomResponder, ErrorCode};
+
+/// Response for a request body that could not be deserialized. Parse errors return their message, so clients
+/// see which field is wrong. IO errors return an empty text
+pub fn json_error_to_responder(jsonerror: JsonError) -> CustomResponder {
+    // Differentiate between different error types
+    let errorstring = match jsonerror {
+        // Result was an IO error. Return an empty String
+        JsonError::Io(_) => { String::from("") }
+        // Result was a parse error. Return the error message as String
+        JsonError::Parse(_, e) => { e.to_string() }
+    };
+    // Return a 422 Error code with a detailed description of the format error.
+    CustomResponder::UnprocessableEntity(Json(json!({"status": {"code": 422,"text": errorstring, "error_code": ErrorCode::InvalidJson}})))
+}
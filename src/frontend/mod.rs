@@ -1,55 +1,87 @@
 use rocket_contrib::templates::Template;
-use std::collections::{HashMap, BTreeMap};
-use crate::user::model::User;
-use hmac::{Hmac, NewMac};
-use jwt::SignWithKey;
-use sha2::Sha256;
-use crate::{DbConn, ApplicationConfig};
-use rocket::http::{Cookie, Cookies};
+use std::collections::HashMap;
+use crate::user::model::{User, PasswordHistory};
+use crate::user::auth;
+use crate::audit;
+use crate::notifications;
+use crate::user::password::PasswordPolicy;
+use crate::{DbConn, ErrorCode, ApplicationConfig, ClientIp, UserAgent, SecuritySettings};
+use crate::security_headers::CspNonce;
+use crate::tenant::CurrentTenant;
+use crate::webhook::{WebhookEvent, WebhookQueue};
+use rocket::http::Cookies;
 use rocket_contrib::templates::tera::Context;
 use rocket::request::Form;
-use bcrypt::{hash, DEFAULT_COST};
+use bcrypt::hash;
+use rocket::State;
+use rocket::response::Redirect;
 
 pub fn mount(rocket: rocket::Rocket) -> rocket::Rocket {
     rocket.mount("/ui", routes![activate, request_reset, reset_password])
         .mount("/ui", routes![activate_error])
 }
 
+/// Response of the pages that log the user in. Either the rendered page or a redirect to the SPA
+#[derive(Responder)]
+enum PageOrRedirect {
+    Page(Template),
+    Redirect(Redirect),
+}
+
+/// Redirect to the configured URL with the issued token as `token` query parameter
+fn redirect_with_token(url: &str, token: &str) -> Redirect {
+    let separator = if url.contains('?') { '&' } else { '?' };
+    Redirect::to(format!("{}{}token={}", url, separator, token))
+}
+
+/// The URL of the configuration key if it is set and not empty
+fn redirect_url(config: &config::Config, key: &str) -> Option<String> {
+    config.get_str(key).ok().filter(|url| !url.is_empty())
+}
+
+/// Render the error page with a human readable message and the machine readable error code
+pub fn specific_error(message: &str, error_code: ErrorCode) -> Template {
+    let mut context = Context::new();
+    context.insert("error_message", message);
+    context.insert("error_code", error_code.as_str());
+    Template::render("error/specific_error", &context)
+}
+
 #[get("/activate/<registration_code>")]
-fn activate(registration_code: String, connection: DbConn, config: ApplicationConfig, mut cookies: Cookies) -> Template {
-    let mut user = match User::by_registration_code(registration_code, &connection.0) {
+fn activate(registration_code: String, nonce: CspNonce, tenant: CurrentTenant, connection: DbConn, config: ApplicationConfig, client_ip: ClientIp, user_agent: UserAgent, mut cookies: Cookies, webhooks: State<WebhookQueue>) -> PageOrRedirect {
+    let mut user = match User::by_registration_code(registration_code, tenant.0, &connection.0) {
         Some(u) => u,
         None => {
-            let mut context = Context::new();
-            context.insert("error_message", "Could not find a user with this registration code");
-            return Template::render("error/specific_error", &context);
-        }
-    };
-    let secretkey = match config.0.get_str("secretkey") {
-        Ok(x) => { x }
-        Err(_) => {
-            error!("Could not find secret key for user token enryption");
-            return Template::render("error/generic_error", &Context::new());
+            return PageOrRedirect::Page(specific_error("Could not find a user with this registration code", ErrorCode::InvalidRegistrationCode));
         }
     };
-    let key: Hmac<Sha256> = Hmac::new_varkey(secretkey.as_bytes()).unwrap();
-    let mut claims = BTreeMap::new();
-    claims.insert("sub", user.id.unwrap().to_string());
-
-    match claims.sign_with_key(&key) {
+    if user.registration_code_expired() {
+        return PageOrRedirect::Page(specific_error("Registration code has expired. Please request a new activation email", ErrorCode::RegistrationCodeExpired));
+    }
+    match auth::issue_token(user.id.unwrap(), client_ip.0.clone(), user_agent.0.clone(), &config.0, &connection.0) {
         Ok(message) => {
-            let cookie = Cookie::build("token", message.clone()).path("/").secure(false).finish();
+            let cookie = auth::session_cookie("token", message.clone(), &config.0);
             cookies.add(cookie);
+            let old = user.clone();
             //Set reset code to null because we have a successful login
             user.registration_code = None;
-            User::update(&user, &connection.0);
+            user.registration_code_expires_at = None;
+            if User::update(&user, &connection.0) {
+                audit::log(&connection.0, user.id.unwrap(), user.id, "activate", &old, &user, client_ip.0.clone());
+                webhooks.dispatch(WebhookEvent::UserActivated, &user, &connection.0);
+            }
+            // a single page application takes over the session instead of the rendered page
+            if let Some(url) = redirect_url(&config.0, "frontend.post_activation_redirect") {
+                return PageOrRedirect::Redirect(redirect_with_token(&url, &message));
+            }
             let mut context = Context::new();
             context.insert("token", &message);
-            Template::render("activate", &context)
+            context.insert("csp_nonce", &nonce.0);
+            PageOrRedirect::Page(Template::render("activate", &context))
         }
-        Err(_) => {
-            error!("Token could not be created");
-            return Template::render("error/generic_error", &Context::new());
+        Err(e) => {
+            error!("Token could not be created: {}", e);
+            return PageOrRedirect::Page(Template::render("error/generic_error", &Context::new()));
         }
     }
 }
@@ -61,17 +93,19 @@ pub fn activate_error(_registration_code: String) -> Template {
 }
 
 #[get("/request_reset/<reset_code>")]
-fn request_reset(reset_code: String, connection: DbConn) -> Template {
-    let user = match User::by_reset_code(reset_code, &connection.0) {
+fn request_reset(reset_code: String, nonce: CspNonce, tenant: CurrentTenant, connection: DbConn) -> Template {
+    let user = match User::by_reset_code(reset_code, tenant.0, &connection.0) {
         Some(u) => u,
         None => {
-            let mut context = Context::new();
-            context.insert("error_message", "Could not find a user with this reset code");
-            return Template::render("error/specific_error", &context);
+            return specific_error("Could not find a user with this reset code", ErrorCode::InvalidResetCode);
         }
     };
+    if user.reset_code_expired() {
+        return specific_error("Reset code has expired", ErrorCode::ResetCodeExpired);
+    }
     let mut context = Context::new();
     context.insert("reset_code", &user.reset_code.unwrap());
+    context.insert("csp_nonce", &nonce.0);
     Template::render("requestResetPassword", &context)
 }
 
@@ -82,49 +116,65 @@ struct ResetForm {
 }
 
 #[post("/reset_password", data = "<resetform>")]
-fn reset_password(resetform: Form<ResetForm>, mut cookies: Cookies, config: ApplicationConfig, connection: DbConn) -> Template {
-    if resetform.password.chars().count() < 8 {
-        let mut context = Context::new();
-        context.insert("error_message", "Password is too short. Minimum 8 characters!");
-        return Template::render("error/specific_error", &context);
+fn reset_password(resetform: Form<ResetForm>, nonce: CspNonce, client_ip: ClientIp, user_agent: UserAgent, mut cookies: Cookies, config: ApplicationConfig, security: State<SecuritySettings>, password_policy: State<PasswordPolicy>, tenant: CurrentTenant, connection: DbConn) -> PageOrRedirect {
+    if let Err((error_code, text)) = password_policy.validate(&resetform.password) {
+        return PageOrRedirect::Page(specific_error(&text, error_code));
     }
-    let mut user = match User::by_reset_code(resetform.reset_code.clone(), &connection.0) {
+    let mut user = match User::by_reset_code(resetform.reset_code.clone(), tenant.0, &connection.0) {
         Some(u) => u,
         None => {
-            let mut context = Context::new();
-            context.insert("error_message", "Could not find a user with this reset code");
-            return Template::render("error/specific_error", &context);
+            return PageOrRedirect::Page(specific_error("Could not find a user with this reset code", ErrorCode::InvalidResetCode));
         }
     };
-    let secretkey = match config.0.get_str("secretkey") {
-        Ok(x) => { x }
-        Err(_) => {
-            error!("Could not find secret key for user token enryption");
-            return Template::render("error/generic_error", &Context::new());
+    if user.reset_code_expired() {
+        return PageOrRedirect::Page(specific_error("Reset code has expired", ErrorCode::ResetCodeExpired));
+    }
+    if PasswordHistory::recently_used(&user, &resetform.password, security.password_history_count, &connection.0) {
+        return PageOrRedirect::Page(specific_error("Password was recently used", ErrorCode::PasswordRecentlyUsed));
+    }
+    // users with two factor authentication get no session, so the reset code alone does not bypass the second
+    // factor. They log in with the new password and a code afterwards
+    let token = if user.totp_enabled {
+        None
+    } else {
+        match auth::issue_token(user.id.unwrap(), client_ip.0.clone(), user_agent.0.clone(), &config.0, &connection.0) {
+            Ok(message) => Some(message),
+            Err(e) => {
+                error!("Token could not be created: {}", e);
+                return PageOrRedirect::Page(Template::render("error/generic_error", &Context::new()));
+            }
         }
     };
-    let key: Hmac<Sha256> = Hmac::new_varkey(secretkey.as_ref()).unwrap();
-    let mut claims = BTreeMap::new();
-    claims.insert("sub", user.id.unwrap().to_string());
-
-    match claims.sign_with_key(&key) {
-        Ok(message) => {
-            let cookie = Cookie::build("token", message.clone()).path("/").secure(false).finish();
-            cookies.add(cookie);
-            user.password = hash(&resetform.password, DEFAULT_COST).unwrap();
-            //Set reset code to null because we have a successful reset
-            user.reset_code = None;
-            // since a user reset was successful, it's also fine to set regestration code to null
-            user.registration_code = None;
-            User::update(&user, &connection.0);
+    if let Some(message) = &token {
+        let cookie = auth::session_cookie("token", message.clone(), &config.0);
+        cookies.add(cookie);
+    }
+    let old = user.clone();
+    user.password = hash(&resetform.password, security.bcrypt_cost).unwrap();
+    PasswordHistory::record(user.id.unwrap(), &user.password, security.password_history_count, &connection.0);
+    //Set reset code to null because we have a successful reset
+    user.reset_code = None;
+    user.reset_code_expires_at = None;
+    // since a user reset was successful, it's also fine to set regestration code to null
+    user.registration_code = None;
+    user.registration_code_expires_at = None;
+    if User::update(&user, &connection.0) {
+        audit::log(&connection.0, user.id.unwrap(), user.id, "reset_password", &old, &user, client_ip.0.clone());
+        notifications::create(&connection.0, user.id.unwrap(), notifications::PASSWORD_CHANGED, &json!({"reset": true, "ip": client_ip.0}));
+    }
+    // a single page application takes over the session instead of the rendered page
+    if let Some(url) = redirect_url(&config.0, "frontend.post_reset_redirect") {
+        return match &token {
+            Some(message) => PageOrRedirect::Redirect(redirect_with_token(&url, message)),
+            None => PageOrRedirect::Redirect(Redirect::to(url))
+        };
+    }
 
-            let mut context = Context::new();
-            context.insert("token", &message);
-            Template::render("resetPassword", &context)
-        }
-        Err(_) => {
-            error!("Token could not be created");
-            return Template::render("error/generic_error", &Context::new());
-        }
+    let mut context = Context::new();
+    context.insert("two_factor_required", &token.is_none());
+    if let Some(message) = &token {
+        context.insert("token", message);
     }
+    context.insert("csp_nonce", &nonce.0);
+    PageOrRedirect::Page(Template::render("resetPassword", &context))
 }
\ No newline at end of file
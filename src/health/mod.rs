@@ -0,0 +1,81 @@
+use diesel::RunQueryDsl;
+use rocket_contrib::json::{Json, JsonValue};
+use crate::{DbConn, CustomResponder, ApplicationConfig};
+use crate::openapi::{Access, RouteDoc};
+
+/// Mount routes for Rocket.
+pub fn mount(rocket: rocket::Rocket) -> rocket::Rocket {
+    rocket.mount("/", routes![health, ready])
+}
+
+/// Describe the routes for the OpenAPI document
+pub fn openapi() -> Vec<RouteDoc> {
+    vec![
+        RouteDoc { json_response: false, ..RouteDoc::new("get", "/health", "Liveness check", Access::Public) },
+        RouteDoc { json_response: false, ..RouteDoc::new("get", "/ready", "Readiness check", Access::Public) },
+    ]
+}
+
+/// Check whether the database answers a trivial query
+fn database_reachable(connection: &Option<DbConn>) -> bool {
+    match connection {
+        Some(connection) => diesel::sql_query("SELECT 1").execute(&connection.0).is_ok(),
+        // no connection could be acquired from the pool
+        None => false
+    }
+}
+
+/// Liveness check. Reports whether the database is reachable
+///
+/// # Arguments
+///
+/// * `connection` - Database connection, if one could be acquired
+///
+/// # Example
+///
+/// ```text
+/// curl --request GET \
+///   --url http://localhost:8000/health
+/// ```
+///
+#[get("/health")]
+fn health(connection: Option<DbConn>) -> Result<Json<JsonValue>, CustomResponder> {
+    if database_reachable(&connection) {
+        Ok(Json(json!({"status": "ok", "db": "connected"})))
+    } else {
+        Err(CustomResponder::ServiceUnavailable(Json(json!({"status": "degraded", "db": "unreachable"}))))
+    }
+}
+
+/// Readiness check. Reports whether the database is reachable and the secret key for tokens is configured
+///
+/// # Arguments
+///
+/// * `connection` - Database connection, if one could be acquired
+/// * `config` - Application configuration, if the configuration file exists
+///
+/// # Example
+///
+/// ```text
+/// curl --request GET \
+///   --url http://localhost:8000/ready
+/// ```
+///
+#[get("/ready")]
+fn ready(connection: Option<DbConn>, config: Option<ApplicationConfig>) -> Result<Json<JsonValue>, CustomResponder> {
+    let db_reachable = database_reachable(&connection);
+    let secretkey_present = match config {
+        Some(config) => config.0.get_str("secretkey").map(|key| !key.is_empty()).unwrap_or(false),
+        None => false
+    };
+    let body = json!({
+        "status": if db_reachable && secretkey_present { "ok" } else { "degraded" },
+        "db": if db_reachable { "connected" } else { "unreachable" },
+        "secretkey": if secretkey_present { "present" } else { "missing" }
+    });
+    if db_reachable && secretkey_present {
+        Ok(Json(body))
+    } else {
+        Err(CustomResponder::ServiceUnavailable(Json(body)))
+    }
+}
\ No newline at end of file
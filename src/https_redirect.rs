@@ -0,0 +1,84 @@
+//! Redirect of HTTP requests to HTTPS. Rocket itself serves plain HTTP, so the scheme of the client is read
+//! from the `X-Forwarded-Proto` header of the reverse proxy that terminates TLS
+use rocket::{Data, Request};
+use rocket::fairing::{Fairing, Info, Kind};
+use rocket::http::Method;
+use rocket::http::uri::Origin;
+use rocket::request::{self, FromRequest};
+use rocket::response::Redirect;
+use rocket::Outcome;
+
+/// Path of the route that answers requests that are redirected to HTTPS
+const HTTPS_REDIRECT_PATH: &str = "/https_redirect";
+/// Paths that are not redirected if `server.https_exclude` is not configured. Probes usually use plain HTTP
+const DEFAULT_EXCLUDED_PATHS: [&str; 2] = ["/health", "/ready"];
+
+/// Attach the fairing and mount the route for redirected requests if `[server] force_https = true` is configured
+pub fn mount(rocket: rocket::Rocket) -> rocket::Rocket {
+    let config = crate::load_config().unwrap_or_default();
+    if !config.get_bool("server.force_https").unwrap_or(false) {
+        return rocket;
+    }
+    let excluded_paths = match config.get_array("server.https_exclude") {
+        Ok(paths) => paths.into_iter().map(|path| match path.into_str() {
+            Ok(path) => path,
+            Err(e) => panic!("server.https_exclude has to be a list of paths: {}", e)
+        }).collect(),
+        Err(_) => DEFAULT_EXCLUDED_PATHS.iter().map(|path| path.to_string()).collect()
+    };
+    rocket
+        .attach(HttpsRedirectFairing { excluded_paths })
+        .mount("/", routes![https_redirect])
+}
+
+/// Fairing that routes HTTP requests to the redirect route
+pub struct HttpsRedirectFairing {
+    /// Paths that are answered over HTTP as well
+    excluded_paths: Vec<String>,
+}
+
+/// HTTPS url of a redirected request. Is stored in the request by the fairing
+struct HttpsTarget(Option<String>);
+
+impl Fairing for HttpsRedirectFairing {
+    fn info(&self) -> Info {
+        Info {
+            name: "HTTPS redirect",
+            kind: Kind::Request,
+        }
+    }
+
+    fn on_request(&self, request: &mut Request, _data: &Data) {
+        let scheme = request.headers().get_one("X-Forwarded-Proto").unwrap_or("http").to_lowercase();
+        if scheme != "http" || self.excluded_paths.iter().any(|path| path == request.uri().path()) {
+            return;
+        }
+        // without a host the https url is not known
+        let host = match request.headers().get_one("Host") {
+            Some(host) => host.to_string(),
+            None => return
+        };
+        let target = format!("https://{}{}", host, request.uri());
+        request.local_cache(|| HttpsTarget(Some(target)));
+        // fairings can not respond themselves, so the request is routed to the redirect route
+        request.set_method(Method::Get);
+        request.set_uri(Origin::parse(HTTPS_REDIRECT_PATH).unwrap());
+    }
+}
+
+impl<'a, 'r> FromRequest<'a, 'r> for &'a HttpsTarget {
+    type Error = ();
+    fn from_request(request: &'a Request<'r>) -> request::Outcome<&'a HttpsTarget, ()> {
+        match request.local_cache(|| HttpsTarget(None)) {
+            target @ HttpsTarget(Some(_)) => Outcome::Success(target),
+            // the route was requested directly
+            HttpsTarget(None) => Outcome::Forward(())
+        }
+    }
+}
+
+/// Redirect route for HTTP requests if `server.force_https` is set
+#[get("/https_redirect")]
+fn https_redirect(target: &HttpsTarget) -> Redirect {
+    Redirect::moved(target.0.clone().unwrap_or_default())
+}
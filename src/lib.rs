@@ -0,0 +1,660 @@
+#![feature(proc_macro_hygiene, decl_macro)]
+
+#[macro_use]
+extern crate rocket;
+#[macro_use]
+extern crate rocket_contrib;
+#[macro_use]
+extern crate serde_derive;
+#[macro_use]
+extern crate diesel;
+#[macro_use]
+extern crate log;
+extern crate serde;
+extern crate bcrypt;
+extern crate config;
+
+use rocket_contrib::templates::Template;
+use rocket_contrib::serve::StaticFiles;
+use rocket_contrib::json::Json;
+use rocket_contrib::json::JsonValue;
+use std::net::IpAddr;
+use config::Config;
+use rocket::{Outcome, State};
+use rocket::request::{self, Request, FromRequest};
+use dotenv::dotenv;
+use rocket::http::Method;
+use rocket::fairing::AdHoc;
+use rocket_cors::{AllowedHeaders, AllowedOrigins, Cors};
+use schemars::JsonSchema;
+
+mod admin;
+mod frontend;
+mod health;
+mod user;
+pub mod mailer;
+mod request_id;
+mod logging;
+mod openapi;
+mod pagination;
+mod storage;
+mod body_limit;
+mod rate_limit;
+mod cors;
+mod webhook;
+mod audit;
+mod timeout;
+mod translations;
+mod route_banner;
+mod error;
+mod https_redirect;
+mod security_headers;
+mod shutdown;
+mod metrics;
+mod notifications;
+mod feedback;
+mod tenant;
+
+#[cfg(any(all(feature = "mysql", feature = "postgres"), all(feature = "mysql", feature = "sqlite"), all(feature = "postgres", feature = "sqlite")))]
+compile_error!("Only one of the features \"mysql\", \"postgres\" and \"sqlite\" can be enabled");
+
+#[cfg(not(any(feature = "mysql", feature = "postgres", feature = "sqlite")))]
+compile_error!("Either the feature \"mysql\", \"postgres\" or \"sqlite\" has to be enabled");
+
+/// Database connection of the selected database backend
+#[cfg(feature = "mysql")]
+pub type Connection = diesel::MysqlConnection;
+/// Database connection of the selected database backend
+#[cfg(feature = "postgres")]
+pub type Connection = diesel::PgConnection;
+/// Database connection of the selected database backend. Only used by the integration tests
+#[cfg(feature = "sqlite")]
+pub type Connection = diesel::SqliteConnection;
+
+#[cfg(feature = "mysql")]
+#[database("webapp_boilerplate")]
+pub struct DbConn(diesel::MysqlConnection);
+
+#[cfg(feature = "postgres")]
+#[database("webapp_boilerplate")]
+pub struct DbConn(diesel::PgConnection);
+
+#[cfg(feature = "sqlite")]
+#[database("webapp_boilerplate")]
+pub struct DbConn(diesel::SqliteConnection);
+
+/// Custom Responder for Errors in the application
+#[derive(Responder, Debug)]
+pub enum CustomResponder {
+    /// An unauthorized access
+    #[response(status = 401)]
+    Unauthorized(Json<JsonValue>),
+    /// The access is not allowed
+    #[response(status = 403)]
+    Forbidden(Json<JsonValue>),
+    /// Something could not be found
+    #[response(status = 404)]
+    NotFound(Json<JsonValue>),
+    /// An Error of some kind occured
+    #[response(status = 500)]
+    InternalServerError(Json<JsonValue>),
+    /// The Request is missing some data
+    #[response(status = 422)]
+    UnprocessableEntity(Json<JsonValue>),
+    /// Data conflict
+    #[response(status = 409)]
+    Conflict(Json<JsonValue>),
+    /// A required service is not available
+    #[response(status = 503)]
+    ServiceUnavailable(Json<JsonValue>),
+    /// The request body or an uploaded file is too large
+    #[response(status = 413)]
+    PayloadTooLarge(Json<JsonValue>),
+    /// The client sent too many requests
+    #[response(status = 429)]
+    TooManyRequests(Json<JsonValue>),
+}
+
+/// Machine readable error codes that are returned as `status.error_code` in every error response.
+/// In contrast to the human readable `status.text` these codes never change.
+#[derive(Serialize, JsonSchema, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum ErrorCode {
+    /// No (valid) token was provided or the user lacks the required permission
+    NotAuthorized,
+    /// The submitted JSON data could not be parsed
+    InvalidJson,
+    /// Email address or password are wrong
+    InvalidCredentials,
+    /// A user with this email address already exists
+    UserAlreadyExists,
+    /// The user could not be found
+    UserNotFound,
+    /// The user is already activated
+    UserAlreadyActivated,
+    /// The password does not have the minimum length
+    PasswordTooShort,
+    /// The password does not meet the character requirements of the password policy
+    PasswordTooWeak,
+    /// The password is the current or one of the recently used passwords
+    PasswordRecentlyUsed,
+    /// The password and its confirmation differ
+    PasswordsDoNotMatch,
+    /// No user with this reset code exists
+    InvalidResetCode,
+    /// The reset code has expired
+    ResetCodeExpired,
+    /// No user with this registration code exists
+    InvalidRegistrationCode,
+    /// The registration code has expired
+    RegistrationCodeExpired,
+    /// Too many failed login attempts for this email address
+    TooManyLoginAttempts,
+    /// No refresh token was provided
+    RefreshTokenMissing,
+    /// No pending email address change exists for this confirmation code
+    InvalidEmailConfirmationCode,
+    /// The refresh token is unknown, expired or revoked
+    InvalidRefreshToken,
+    /// A token could not be created
+    TokenCreationFailed,
+    /// The uploaded file is not a valid image
+    InvalidImage,
+    /// The user has no profile image
+    ProfileImageNotFound,
+    /// The session could not be found
+    SessionNotFound,
+    /// Two factor authentication is already enabled
+    TwoFactorAlreadyEnabled,
+    /// Two factor authentication has not been set up
+    TwoFactorNotSetUp,
+    /// Two factor authentication could not be set up
+    TwoFactorSetupFailed,
+    /// The code of the authenticator app is wrong
+    InvalidTwoFactorCode,
+    /// The user has no phone number
+    PhoneMissing,
+    /// The phone number of the user is already verified
+    PhoneAlreadyVerified,
+    /// The SMS could not be sent
+    SmsFailed,
+    /// The OTP from the SMS is wrong or expired
+    InvalidOtp,
+    /// The interim token of a login with two factor authentication is unknown or expired
+    InvalidInterimToken,
+    /// Registration is only possible with a valid invitation
+    InvitationRequired,
+    /// The state of a login with Google is missing, expired or does not match
+    InvalidOAuthState,
+    /// The login with Google failed or was cancelled
+    OAuthLoginFailed,
+    /// The api key does not exist or belongs to another user
+    ApiKeyNotFound,
+    /// The action is not allowed with the token of an impersonating admin
+    ImpersonationForbidden,
+    /// An uploaded file could not be stored or loaded
+    StorageError,
+    /// The request body or an uploaded file exceeds the configured size limit
+    PayloadTooLarge,
+    /// The client exceeded the rate limit of the route
+    TooManyRequests,
+    /// The account was suspended by an admin
+    AccountSuspended,
+    /// The url or the events of a webhook are invalid
+    InvalidWebhook,
+    /// The webhook does not exist
+    WebhookNotFound,
+    /// The sort column or sort order of a list is not supported
+    InvalidSortOrder,
+    /// The username does not match the configured pattern
+    InvalidUsername,
+    /// The username is used by another user
+    UsernameTaken,
+    /// The timezone is not in the IANA timezone database
+    InvalidTimezone,
+    /// The locale is not in the configured list of supported locales
+    UnsupportedLocale,
+    /// There is no mail template with this name
+    TemplateNotFound,
+    /// The mail template could not be rendered
+    TemplateError,
+    /// The pagination cursor is not valid
+    InvalidCursor,
+    /// The user has not clicked the activation link yet
+    AccountNotActivated,
+    /// The metrics could not be encoded
+    MetricsError,
+    /// The bio is longer than 500 characters
+    BioTooLong,
+    /// The website is not an http or https URL
+    InvalidWebsite,
+    /// The avatar URL is not an https URL
+    InvalidAvatarUrl,
+    /// The OAuth provider is not supported
+    UnsupportedOAuthProvider,
+    /// The account of the OAuth provider is linked to another user or the user has already linked the provider
+    OAuthAccountAlreadyLinked,
+    /// The user has no linked account of the OAuth provider
+    OAuthAccountNotFound,
+    /// The action needs a password but the user has not set one
+    PasswordRequired,
+    /// The user has no notification with the id
+    NotificationNotFound,
+    /// The unsubscribe link is malformed or its signature is invalid
+    InvalidUnsubscribeToken,
+    /// The tenant slug is not a lowercase subdomain label
+    InvalidTenantSlug,
+    /// A tenant with the slug already exists
+    TenantAlreadyExists,
+    /// There is no tenant with the id
+    TenantNotFound,
+    /// The tenant can not be deleted because it still has users
+    TenantHasUsers,
+    /// Only admins without a tenant can perform the action
+    PlatformAdminRequired,
+    /// Admins can not remove their own admin role
+    CannotDemoteSelf,
+    /// Admins can not delete their own account through the admin routes
+    CannotDeleteSelf,
+    /// The feedback message is empty or too long
+    InvalidFeedback,
+    /// A database operation failed
+    DatabaseError,
+}
+
+impl ErrorCode {
+    /// The error code as it is sent to the client
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ErrorCode::NotAuthorized => "NOT_AUTHORIZED",
+            ErrorCode::InvalidJson => "INVALID_JSON",
+            ErrorCode::InvalidCredentials => "INVALID_CREDENTIALS",
+            ErrorCode::UserAlreadyExists => "USER_ALREADY_EXISTS",
+            ErrorCode::UserNotFound => "USER_NOT_FOUND",
+            ErrorCode::UserAlreadyActivated => "USER_ALREADY_ACTIVATED",
+            ErrorCode::PasswordTooShort => "PASSWORD_TOO_SHORT",
+            ErrorCode::PasswordTooWeak => "PASSWORD_TOO_WEAK",
+            ErrorCode::PasswordRecentlyUsed => "PASSWORD_RECENTLY_USED",
+            ErrorCode::PasswordsDoNotMatch => "PASSWORDS_DO_NOT_MATCH",
+            ErrorCode::InvalidResetCode => "INVALID_RESET_CODE",
+            ErrorCode::ResetCodeExpired => "RESET_CODE_EXPIRED",
+            ErrorCode::InvalidRegistrationCode => "INVALID_REGISTRATION_CODE",
+            ErrorCode::RegistrationCodeExpired => "REGISTRATION_CODE_EXPIRED",
+            ErrorCode::TooManyLoginAttempts => "TOO_MANY_LOGIN_ATTEMPTS",
+            ErrorCode::InvalidEmailConfirmationCode => "INVALID_EMAIL_CONFIRMATION_CODE",
+            ErrorCode::RefreshTokenMissing => "REFRESH_TOKEN_MISSING",
+            ErrorCode::InvalidRefreshToken => "INVALID_REFRESH_TOKEN",
+            ErrorCode::TokenCreationFailed => "TOKEN_CREATION_FAILED",
+            ErrorCode::InvalidImage => "INVALID_IMAGE",
+            ErrorCode::ProfileImageNotFound => "PROFILE_IMAGE_NOT_FOUND",
+            ErrorCode::SessionNotFound => "SESSION_NOT_FOUND",
+            ErrorCode::TwoFactorAlreadyEnabled => "TWO_FACTOR_ALREADY_ENABLED",
+            ErrorCode::TwoFactorNotSetUp => "TWO_FACTOR_NOT_SET_UP",
+            ErrorCode::TwoFactorSetupFailed => "TWO_FACTOR_SETUP_FAILED",
+            ErrorCode::InvalidTwoFactorCode => "INVALID_TWO_FACTOR_CODE",
+            ErrorCode::PhoneMissing => "PHONE_MISSING",
+            ErrorCode::PhoneAlreadyVerified => "PHONE_ALREADY_VERIFIED",
+            ErrorCode::SmsFailed => "SMS_FAILED",
+            ErrorCode::InvalidOtp => "INVALID_OTP",
+            ErrorCode::InvalidInterimToken => "INVALID_INTERIM_TOKEN",
+            ErrorCode::InvitationRequired => "INVITATION_REQUIRED",
+            ErrorCode::InvalidOAuthState => "INVALID_OAUTH_STATE",
+            ErrorCode::OAuthLoginFailed => "OAUTH_LOGIN_FAILED",
+            ErrorCode::ApiKeyNotFound => "API_KEY_NOT_FOUND",
+            ErrorCode::ImpersonationForbidden => "IMPERSONATION_FORBIDDEN",
+            ErrorCode::StorageError => "STORAGE_ERROR",
+            ErrorCode::PayloadTooLarge => "PAYLOAD_TOO_LARGE",
+            ErrorCode::TooManyRequests => "TOO_MANY_REQUESTS",
+            ErrorCode::AccountSuspended => "ACCOUNT_SUSPENDED",
+            ErrorCode::InvalidWebhook => "INVALID_WEBHOOK",
+            ErrorCode::WebhookNotFound => "WEBHOOK_NOT_FOUND",
+            ErrorCode::InvalidSortOrder => "INVALID_SORT_ORDER",
+            ErrorCode::InvalidUsername => "INVALID_USERNAME",
+            ErrorCode::UsernameTaken => "USERNAME_TAKEN",
+            ErrorCode::InvalidTimezone => "INVALID_TIMEZONE",
+            ErrorCode::UnsupportedLocale => "UNSUPPORTED_LOCALE",
+            ErrorCode::TemplateNotFound => "TEMPLATE_NOT_FOUND",
+            ErrorCode::TemplateError => "TEMPLATE_ERROR",
+            ErrorCode::InvalidCursor => "INVALID_CURSOR",
+            ErrorCode::AccountNotActivated => "ACCOUNT_NOT_ACTIVATED",
+            ErrorCode::MetricsError => "METRICS_ERROR",
+            ErrorCode::BioTooLong => "BIO_TOO_LONG",
+            ErrorCode::InvalidWebsite => "INVALID_WEBSITE",
+            ErrorCode::InvalidAvatarUrl => "INVALID_AVATAR_URL",
+            ErrorCode::UnsupportedOAuthProvider => "UNSUPPORTED_OAUTH_PROVIDER",
+            ErrorCode::OAuthAccountAlreadyLinked => "OAUTH_ACCOUNT_ALREADY_LINKED",
+            ErrorCode::OAuthAccountNotFound => "OAUTH_ACCOUNT_NOT_FOUND",
+            ErrorCode::PasswordRequired => "PASSWORD_REQUIRED",
+            ErrorCode::NotificationNotFound => "NOTIFICATION_NOT_FOUND",
+            ErrorCode::InvalidUnsubscribeToken => "INVALID_UNSUBSCRIBE_TOKEN",
+            ErrorCode::InvalidTenantSlug => "INVALID_TENANT_SLUG",
+            ErrorCode::TenantAlreadyExists => "TENANT_ALREADY_EXISTS",
+            ErrorCode::TenantNotFound => "TENANT_NOT_FOUND",
+            ErrorCode::TenantHasUsers => "TENANT_HAS_USERS",
+            ErrorCode::PlatformAdminRequired => "PLATFORM_ADMIN_REQUIRED",
+            ErrorCode::CannotDemoteSelf => "CANNOT_DEMOTE_SELF",
+            ErrorCode::CannotDeleteSelf => "CANNOT_DELETE_SELF",
+            ErrorCode::InvalidFeedback => "INVALID_FEEDBACK",
+            ErrorCode::DatabaseError => "DATABASE_ERROR",
+        }
+    }
+}
+
+/// Origins that are allowed if no `[cors] allowed_origins` are configured
+const DEFAULT_ALLOWED_ORIGINS: [&str; 2] = ["http://localhost:3000", "http://127.0.0.1:3000"];
+
+/// Read the allowed CORS origins from the configuration file
+fn allowed_origins() -> Vec<String> {
+    let configured = match load_config() {
+        Ok(config) => config.get_array("cors.allowed_origins").ok(),
+        Err(_) => None
+    };
+    match configured {
+        Some(origins) => origins.into_iter().filter_map(|o| o.into_str().ok()).collect(),
+        None => {
+            warn!("No [cors] allowed_origins configured. Falling back to {:?}", DEFAULT_ALLOWED_ORIGINS);
+            DEFAULT_ALLOWED_ORIGINS.iter().map(|o| o.to_string()).collect()
+        }
+    }
+}
+
+/// Request headers that are allowed for cross origin requests
+pub const CORS_ALLOWED_HEADERS: [&str; 7] = ["Content-Type", "Authorization", "X-Api-Key", "X-Request-ID", "If-None-Match", "Accept", "Access-Control-Allow-Origin"];
+/// Response headers that clients can read: the request id for bug reports and the ETag for conditional requests
+pub const CORS_EXPOSE_HEADERS: [&str; 2] = [request_id::REQUEST_ID_HEADER, "ETag"];
+
+fn make_cors() -> Cors {
+    let origins = allowed_origins();
+    let origins: Vec<&str> = origins.iter().map(|o| o.as_str()).collect();
+    let (allowed_origins, _failed_origins) = AllowedOrigins::some(&origins);
+
+    rocket_cors::Cors {
+        allowed_origins,
+        allowed_methods: vec![Method::Get, Method::Post, Method::Put, Method::Patch, Method::Delete].into_iter().map(From::from).collect(),
+        allowed_headers: AllowedHeaders::some(&CORS_ALLOWED_HEADERS),
+        expose_headers: CORS_EXPOSE_HEADERS.iter().map(|h| h.to_string()).collect(),
+        allow_credentials: true,
+        ..Default::default()
+    }
+}
+
+
+/// Prefix of environment variables that override values of `Config.toml`
+const ENV_PREFIX: &str = "APP";
+
+/// Read the configuration from `Config.toml`. Environment variables with the prefix `APP_` take precedence,
+/// nested keys are separated by `__`, e.g. `APP_EMAIL__SMTP_USERNAME` for `email.smtp_username`
+pub fn load_config() -> Result<Config, config::ConfigError> {
+    let mut settings = config::Config::default();
+    // the file is optional because the whole configuration can be provided by environment variables
+    settings.merge(config::File::with_name("Config").required(false))?;
+    settings.merge(config::Environment::with_prefix(ENV_PREFIX).separator("__"))?;
+    Ok(settings)
+}
+
+/// Keys that have to be present in `Config.toml`. All other keys are optional and have defaults
+const REQUIRED_CONFIG_KEYS: [&str; 6] = [
+    "secretkey",
+    "email.smtp_username",
+    "email.smtp_password",
+    "email.smtp_hostname",
+    "email.smtp_port",
+    "email.smtp_sending_address",
+];
+
+/// Check at startup that the configuration file exists and contains all required keys. Panics otherwise
+fn validate_config() {
+    let configuration = match load_config() {
+        Ok(config) => config,
+        Err(e) => panic!("Configuration could not be read: {}", e)
+    };
+    for key in REQUIRED_CONFIG_KEYS.iter() {
+        if configuration.get::<config::Value>(key).is_err() {
+            panic!("Required configuration key {} is missing in Config.toml and environment", key);
+        }
+    }
+    // an empty secret key would make all tokens forgeable
+    if configuration.get_str("secretkey").map(|key| key.is_empty()).unwrap_or(true) {
+        panic!("Configuration key secretkey must not be empty");
+    }
+}
+
+/// Log the effective size of the database pool from `databases.webapp_boilerplate` in `Rocket.toml` or
+/// `ROCKET_DATABASES`, so operators can confirm that their settings are used
+fn log_database_config(rocket: &rocket::Rocket) {
+    match rocket_contrib::databases::database_config("webapp_boilerplate", rocket.config()) {
+        Ok(database) => {
+            info!("Database pool size: {}", database.pool_size);
+            // rocket_contrib 0.4 always waits 30 seconds for a free connection
+            if database.extras.contains_key("timeout") {
+                warn!("databases.webapp_boilerplate.timeout is not supported by Rocket 0.4 and is ignored");
+            }
+        }
+        Err(e) => error!("Database configuration could not be read: {}", e)
+    }
+}
+
+/// Read the configuration and start the server
+pub fn run() {
+    dotenv().ok();
+    logging::init();
+    validate_config();
+    // operators send SIGTERM to this process to shut down after the queued mails are sent
+    info!("Starting with PID {}", std::process::id());
+    let rocket = build(rocket::ignite());
+    shutdown::install(&rocket);
+    rocket.launch();
+}
+
+/// Attach the fairings, managed state and routes of the application to a Rocket instance. The server uses the
+/// settings of `Rocket.toml`, the integration tests pass an instance with their own database
+pub fn build(rocket: rocket::Rocket) -> rocket::Rocket {
+    let mut rocket = rocket
+        .attach(DbConn::fairing())
+        .attach(AdHoc::on_attach("Database pool settings", |rocket| {
+            log_database_config(&rocket);
+            Ok(rocket)
+        }))
+        .attach(Template::fairing())
+        .attach(request_id::RequestIdFairing)
+        .manage(SecuritySettings::from_config())
+        .manage(TrustedProxies::from_config())
+        .manage(user::password::PasswordPolicy::from_config())
+        .manage(user::username::UsernamePolicy::from_config())
+        .manage(ImageSettings::from_config())
+        .mount("/assets", StaticFiles::from("templates/assets/"));
+    // HTTP requests are redirected before any other fairing handles them
+    rocket = https_redirect::mount(rocket);
+    // the allowed origins are either configured in Config.toml or read from the database
+    rocket = if cors::dynamic_mode() {
+        cors::mount(rocket)
+    } else {
+        rocket
+            .mount("/", rocket_cors::catch_all_options_routes())
+            .manage(make_cors())
+            .attach(make_cors())
+    };
+    // the mail queue keeps its own database connection for logging the sent mails
+    rocket = rocket.attach(AdHoc::on_attach("Mail queue", |rocket| {
+        let connection = DbConn::get_one(&rocket);
+        Ok(rocket.manage(mailer::MailQueue::from_config(connection)))
+    }));
+    // the webhook queue keeps its own database connection for logging the deliveries
+    rocket = rocket.attach(AdHoc::on_attach("Webhook queue", |rocket| {
+        let connection = DbConn::get_one(&rocket);
+        Ok(rocket.manage(webhook::WebhookQueue::start(connection)))
+    }));
+    // remove revoked tokens that are expired anyway
+    rocket = rocket.attach(AdHoc::on_launch("Revoked token cleanup", |rocket| {
+        if let Some(connection) = DbConn::get_one(rocket) {
+            user::model::RevokedToken::delete_expired(&connection.0);
+        }
+    }));
+    // remove the users whose deletion grace period is over. The cleanup keeps its own database connection and
+    // runs periodically in the background
+    rocket = rocket.attach(AdHoc::on_launch("Deleted user cleanup", |rocket| {
+        if let (Some(connection), Some(webhooks)) = (DbConn::get_one(rocket), rocket.state::<webhook::WebhookQueue>()) {
+            admin::start_purge(connection, webhooks.clone());
+        }
+    }));
+    rocket = user::mount(rocket);
+    rocket = admin::mount(rocket);
+    rocket = frontend::mount(rocket);
+    rocket = health::mount(rocket);
+    rocket = openapi::mount(rocket);
+    rocket = webhook::mount(rocket);
+    rocket = notifications::mount(rocket);
+    rocket = feedback::mount(rocket);
+    rocket = storage::mount(rocket);
+    rocket = body_limit::mount(rocket);
+    rocket = rate_limit::mount(rocket);
+    rocket = timeout::mount(rocket);
+    rocket = metrics::mount(rocket);
+    rocket = security_headers::mount(rocket);
+    // attached last, so all routes are mounted
+    route_banner::mount(rocket)
+}
+
+#[derive(Debug)]
+pub struct ApplicationConfig(pub Config);
+
+impl<'a, 'r> FromRequest<'a, 'r> for ApplicationConfig {
+    type Error = ();
+    fn from_request(_request: &'a Request<'r>) -> request::Outcome<ApplicationConfig, ()> {
+        match load_config() {
+            Ok(config) => {
+                Outcome::Success(ApplicationConfig { 0: config.clone() })
+            }
+            Err(_) => { Outcome::Forward(()) }
+        }
+    }
+}
+
+/// Addresses of the reverse proxies whose `X-Real-IP` header is used as the client ip. Are read once at startup
+/// and stored in Rocket's managed state
+#[derive(Debug, Default)]
+pub struct TrustedProxies(pub Vec<IpAddr>);
+
+impl TrustedProxies {
+    /// Read `http.trusted_proxies` from the configuration file. Panics on invalid addresses
+    pub fn from_config() -> TrustedProxies {
+        match load_config().ok().and_then(|c| c.get_array("http.trusted_proxies").ok()) {
+            Some(proxies) => TrustedProxies(proxies.into_iter().map(|proxy| match proxy.into_str().map(|proxy| proxy.parse::<IpAddr>()) {
+                Ok(Ok(ip)) => ip,
+                _ => panic!("http.trusted_proxies has to be a list of ip addresses")
+            }).collect()),
+            None => TrustedProxies::default()
+        }
+    }
+
+    /// The ip of the client that sent the request. `Request::client_ip()` prefers the `X-Real-IP` header that every
+    /// client can set, so the header is only used for requests from a trusted proxy
+    pub fn client_ip(&self, request: &Request) -> Option<IpAddr> {
+        let remote = request.remote()?.ip();
+        if self.0.contains(&remote) {
+            request.real_ip().or(Some(remote))
+        } else {
+            Some(remote)
+        }
+    }
+}
+
+/// The IP address of the client that sent the request, if known. `X-Real-IP` is only used from trusted proxies
+#[derive(Debug)]
+pub struct ClientIp(pub Option<String>);
+
+impl<'a, 'r> FromRequest<'a, 'r> for ClientIp {
+    type Error = ();
+    fn from_request(request: &'a Request<'r>) -> request::Outcome<ClientIp, ()> {
+        let ip = match request.guard::<State<TrustedProxies>>().succeeded() {
+            Some(proxies) => proxies.client_ip(request),
+            None => request.remote().map(|remote| remote.ip())
+        };
+        Outcome::Success(ClientIp(ip.map(|ip| ip.to_string())))
+    }
+}
+
+/// The user agent of the client that sent the request, if known
+#[derive(Debug)]
+pub struct UserAgent(pub Option<String>);
+
+impl<'a, 'r> FromRequest<'a, 'r> for UserAgent {
+    type Error = ();
+    fn from_request(request: &'a Request<'r>) -> request::Outcome<UserAgent, ()> {
+        Outcome::Success(UserAgent(request.headers().get_one("User-Agent").map(|u| u.to_string())))
+    }
+}
+
+/// Security settings that are read once at startup and stored in Rocket's managed state
+#[derive(Debug)]
+pub struct SecuritySettings {
+    /// Work factor for bcrypt password hashes
+    pub bcrypt_cost: u32,
+    /// Number of characters of a generated registration code
+    pub registration_code_length: usize,
+    /// Number of characters of a generated reset code
+    pub reset_code_length: usize,
+    /// Number of previous passwords that can not be used again
+    pub password_history_count: usize,
+}
+
+/// Read a code length from the configuration. Codes are stored in VARCHAR(255) columns
+fn code_length(configuration: Option<&Config>, key: &str, default: usize) -> usize {
+    match configuration.and_then(|c| c.get_int(key).ok()) {
+        Some(length) if (8..=255).contains(&length) => length as usize,
+        Some(length) => panic!("{} has to be between 8 and 255 but is {}", key, length),
+        None => default
+    }
+}
+
+impl SecuritySettings {
+    /// Read the `[security]` settings from the configuration file. Panics on invalid values
+    pub fn from_config() -> SecuritySettings {
+        let configuration = load_config().ok();
+        let bcrypt_cost = match configuration.as_ref().and_then(|c| c.get_int("security.bcrypt_cost").ok()) {
+            Some(cost) if (4..=31).contains(&cost) => cost as u32,
+            Some(cost) => panic!("security.bcrypt_cost has to be between 4 and 31 but is {}", cost),
+            None => bcrypt::DEFAULT_COST
+        };
+        let registration_code_length = code_length(configuration.as_ref(), "security.registration_code_length", 32);
+        let reset_code_length = code_length(configuration.as_ref(), "security.reset_code_length", 16);
+        let password_history_count = match configuration.as_ref().and_then(|c| c.get_int("security.password_history_count").ok()) {
+            Some(count) if count >= 0 => count as usize,
+            Some(count) => panic!("security.password_history_count must not be negative but is {}", count),
+            None => 5
+        };
+        SecuritySettings { bcrypt_cost, registration_code_length, reset_code_length, password_history_count }
+    }
+}
+
+/// Default quality of generated JPEG images if `image.jpeg_quality` is not configured
+const DEFAULT_JPEG_QUALITY: u8 = 85;
+/// Default maximum size of an uploaded image if `image.max_upload_bytes` is not configured
+const DEFAULT_MAX_UPLOAD_BYTES: u64 = 5 * 1024 * 1024;
+
+/// Image settings that are read once at startup and stored in Rocket's managed state
+#[derive(Debug)]
+pub struct ImageSettings {
+    /// Quality of generated JPEG images (0-100)
+    pub jpeg_quality: u8,
+    /// Maximum size of an uploaded image file in bytes
+    pub max_upload_bytes: u64,
+}
+
+impl ImageSettings {
+    /// Read the `[image]` settings from the configuration file. Panics on invalid values
+    pub fn from_config() -> ImageSettings {
+        let configuration = load_config().ok();
+        let jpeg_quality = match configuration.as_ref().and_then(|c| c.get_int("image.jpeg_quality").ok()) {
+            Some(quality) if (0..=100).contains(&quality) => quality as u8,
+            Some(quality) => panic!("image.jpeg_quality has to be between 0 and 100 but is {}", quality),
+            None => DEFAULT_JPEG_QUALITY
+        };
+        let max_upload_bytes = match configuration.as_ref().and_then(|c| c.get_int("image.max_upload_bytes").ok()) {
+            Some(bytes) if bytes > 0 => bytes as u64,
+            Some(bytes) => panic!("image.max_upload_bytes has to be positive but is {}", bytes),
+            None => DEFAULT_MAX_UPLOAD_BYTES
+        };
+        ImageSettings { jpeg_quality, max_upload_bytes }
+    }
+}
@@ -0,0 +1,59 @@
+//! Logging setup. Log lines are written to stdout as JSON objects so log aggregators can parse their fields.
+//! A `log4rs.yml` in the working directory takes precedence over the JSON logging
+use std::error::Error;
+use std::path::Path;
+
+use chrono::Utc;
+use log::{LevelFilter, Record};
+use log4rs::append::console::ConsoleAppender;
+use log4rs::config::{Appender, Config, Root};
+use log4rs::encode::{Encode, Write};
+
+use crate::request_id;
+
+/// File with a log4rs configuration that is used instead of the JSON logging if it exists
+const LOG4RS_CONFIG_FILE: &str = "log4rs.yml";
+/// Log level if `logging.level` is not configured
+const DEFAULT_LEVEL: LevelFilter = LevelFilter::Warn;
+
+/// Encoder that writes one JSON object per line with the keys `timestamp`, `level`, `module`, `message` and
+/// `request_id`. The `JsonEncoder` of log4rs has fixed field names and can not add the request id as top level key
+#[derive(Debug)]
+pub struct JsonLogEncoder;
+
+impl Encode for JsonLogEncoder {
+    fn encode(&self, w: &mut dyn Write, record: &Record) -> Result<(), Box<dyn Error + Sync + Send>> {
+        let line = serde_json::json!({
+            "timestamp": Utc::now().to_rfc3339(),
+            "level": record.level().to_string(),
+            "module": record.module_path().unwrap_or_else(|| record.target()),
+            "message": record.args().to_string(),
+            "request_id": request_id::current(),
+        });
+        w.write_all(line.to_string().as_bytes())?;
+        w.write_all(b"\n")?;
+        Ok(())
+    }
+}
+
+/// Read the log level from `logging.level` in the configuration file
+fn level() -> LevelFilter {
+    match crate::load_config().ok().and_then(|config| config.get_str("logging.level").ok()) {
+        Some(level) => level.parse().unwrap_or(DEFAULT_LEVEL),
+        None => DEFAULT_LEVEL
+    }
+}
+
+/// Initialise the logger. Uses `log4rs.yml` if the file exists and JSON logging to stdout otherwise
+pub fn init() {
+    if Path::new(LOG4RS_CONFIG_FILE).exists() {
+        log4rs::init_file(LOG4RS_CONFIG_FILE, Default::default()).unwrap();
+        return;
+    }
+    let stdout = ConsoleAppender::builder().encoder(Box::new(JsonLogEncoder)).build();
+    let config = Config::builder()
+        .appender(Appender::builder().build("stdout", Box::new(stdout)))
+        .build(Root::builder().appender("stdout").build(level()))
+        .unwrap();
+    log4rs::init_config(config).unwrap();
+}
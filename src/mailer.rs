@@ -6,6 +6,16 @@ extern crate native_tls;
 use std::borrow::Borrow;
 use std::env;
 use std::string::ToString;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use chrono::Utc;
+use chrono_tz::Tz;
+use crossbeam_channel::{unbounded, Sender};
+use hmac::{Hmac, Mac, NewMac};
+use sha2::Sha256;
 
 use lettre::{
     ClientSecurity, ClientTlsParameters, SmtpClient, Transport,
@@ -16,7 +26,10 @@ use mime::Mime;
 use native_tls::{Protocol, TlsConnector};
 use rocket_contrib::templates::tera::{Context, Tera};
 
-use crate::user::model::User;
+use crate::{Connection, DbConn};
+use crate::request_id;
+use crate::translations;
+use crate::user::model::{EmailLog, EmailPreference, User};
 
 use self::lettre::smtp::error::SmtpResult;
 use self::lettre_email::Email;
@@ -28,6 +41,9 @@ struct SmtpCredentials {
     hostname: String,
     port: i32,
     sending_address: String,
+    /// Display name in the `From` header, e.g. `My App`. Only the address is sent if it is missing
+    from_name: Option<String>,
+    tls_mode: String,
 }
 
 ///email attached file
@@ -40,11 +56,312 @@ pub struct AttachedFile {
     pub content_type: Mime
 }
 
+/// A mail that waits in the queue for being sent
+pub struct EmailJob {
+    /// Id of the user the mail belongs to. Not every mail has to belong to a user
+    pub user_id: Option<i32>,
+    /// Email address of the recipient
+    pub email: String,
+    /// Context for rendering the template
+    pub context: Context,
+    /// Name of the tera template in `templates_mail` without extension
+    pub template: String,
+    /// Subject of the mail
+    pub subject: String,
+    /// Attached files
+    pub attachments: Option<Vec<AttachedFile>>,
+    /// Id of the request that queued the mail. Log lines of the mail queue contain it
+    pub request_id: String,
+}
+
+/// A mail that was captured by the `MockMailer` instead of being sent
+#[derive(Clone, Debug)]
+pub struct SentEmail {
+    /// Email address of the recipient
+    pub to: String,
+    /// Translated subject
+    pub subject: String,
+    /// Name of the tera template in `templates_mail` without extension
+    pub template: String,
+    /// Names of the variables in the context of the template
+    pub context_keys: Vec<String>,
+    /// Context of the template as JSON, e.g. to check the registration code
+    pub context: serde_json::Value,
+}
+
+/// Backend that keeps the mails in memory instead of sending them. Clones share the same list of mails
+#[derive(Clone, Default)]
+pub struct MockMailer(Arc<Mutex<Vec<SentEmail>>>);
+
+impl MockMailer {
+    /// All mails that were sent so far, the oldest first
+    pub fn sent_emails(&self) -> Vec<SentEmail> {
+        self.0.lock().unwrap().clone()
+    }
+
+    /// Remember a mail instead of sending it
+    fn send(&self, job: EmailJob) {
+        let context = serde_json::to_value(&job.context).unwrap_or(serde_json::Value::Null);
+        let context_keys = match &context {
+            serde_json::Value::Object(fields) => fields.keys().cloned().collect(),
+            _ => Vec::new()
+        };
+        self.0.lock().unwrap().push(SentEmail { to: job.email, subject: job.subject, template: job.template, context_keys, context });
+    }
+}
+
+/// Backend that delivers the queued mails. Selected with `email.backend`
+pub enum MailBackend {
+    /// Mails are sent with the configured SMTP server by a background thread
+    Smtp(Sender<EmailJob>),
+    /// Mails are only kept in memory. Used by the integration tests
+    Mock(MockMailer),
+}
+
+/// Number of mails that are not sent yet and whether the server is shutting down. Clones share the state, so the
+/// signal handler can wait for the queue
+#[derive(Clone, Default)]
+pub struct MailDrain {
+    pending: Arc<AtomicUsize>,
+    shutting_down: Arc<AtomicBool>,
+}
+
+impl MailDrain {
+    /// Stop retrying failed mails and wait until the queued mails are sent or the timeout is over.
+    /// Returns the number of mails that were not sent
+    pub fn drain(&self, timeout: Duration) -> usize {
+        self.shutting_down.store(true, Ordering::SeqCst);
+        let started_at = Instant::now();
+        while self.pending.load(Ordering::SeqCst) > 0 && started_at.elapsed() < timeout {
+            thread::sleep(Duration::from_millis(100));
+        }
+        self.pending.load(Ordering::SeqCst)
+    }
+
+    /// Number of mails that are queued or being sent
+    pub fn pending(&self) -> usize {
+        self.pending.load(Ordering::SeqCst)
+    }
+}
+
+/// Queue for sending mails in a background thread. Is stored in Rocket's managed state
+pub struct MailQueue {
+    backend: MailBackend,
+    drain: MailDrain,
+}
+
+/// Default number of retries for a failed mail if `email.max_retries` is not configured
+const DEFAULT_MAX_RETRIES: u32 = 3;
+
+impl MailQueue {
+    /// Spawn the background thread that sends the queued mails. Failed mails are retried with exponential backoff.
+    /// Every attempt is recorded in the email log if a database connection is provided
+    pub fn start(max_retries: u32, connection: Option<DbConn>) -> MailQueue {
+        let (sender, receiver) = unbounded::<EmailJob>();
+        let drain = MailDrain::default();
+        let worker_drain = drain.clone();
+        thread::spawn(move || {
+            if connection.is_none() {
+                warn!("[{}] No database connection for the mail queue. Sent mails are not logged", request_id::current());
+            }
+            for job in receiver.iter() {
+                let mut attempt = 0;
+                loop {
+                    let result = match deliver(&job) {
+                        Ok(Ok(_)) => Ok(()),
+                        Ok(Err(e)) => Err(e.to_string()),
+                        Err(e) => Err(e)
+                    };
+                    if let Some(connection) = &connection {
+                        if !EmailLog::create(job.user_id, &job.template, &job.subject, result.clone().err(), &connection.0) {
+                            error!("[{}] Mail attempt to {} could not be logged", job.request_id, job.email);
+                        }
+                    }
+                    match result {
+                        Ok(_) => break,
+                        // during a shutdown the queue is not blocked by retries
+                        Err(e) if worker_drain.shutting_down.load(Ordering::SeqCst) => {
+                            error!("[{}] Mail to {} could not be sent before the shutdown: {}", job.request_id, job.email, e);
+                            break;
+                        }
+                        Err(e) if attempt < max_retries => warn!("[{}] Mail to {} could not be sent: {}. Retrying", job.request_id, job.email, e),
+                        Err(e) => {
+                            error!("[{}] Mail to {} could not be sent after {} retries: {}", job.request_id, job.email, max_retries, e);
+                            break;
+                        }
+                    }
+                    // wait 1, 2, 4, ... seconds before the next attempt
+                    thread::sleep(Duration::from_secs(1 << attempt.min(10)));
+                    attempt += 1;
+                }
+                worker_drain.pending.fetch_sub(1, Ordering::SeqCst);
+            }
+        });
+        MailQueue { backend: MailBackend::Smtp(sender), drain }
+    }
+
+    /// A queue that captures the mails with a `MockMailer` instead of sending them
+    pub fn mock() -> MailQueue {
+        MailQueue { backend: MailBackend::Mock(MockMailer::default()), drain: MailDrain::default() }
+    }
+
+    /// Handle for waiting until the queued mails are sent
+    pub fn drain_handle(&self) -> MailDrain {
+        self.drain.clone()
+    }
+
+    /// The mock backend if mails are not sent with SMTP
+    pub fn mock_mailer(&self) -> Option<&MockMailer> {
+        match &self.backend {
+            MailBackend::Mock(mock) => Some(mock),
+            MailBackend::Smtp(_) => None
+        }
+    }
+
+    /// Start the queue with the number of retries from `email.max_retries` in the configuration file. Uses the
+    /// `MockMailer` if `email.backend` is `"mock"`. Panics if `email.smtp_port` is not a valid port
+    pub fn from_config(connection: Option<DbConn>) -> MailQueue {
+        let backend = crate::load_config().ok().and_then(|c| c.get_str("email.backend").ok());
+        match backend.as_deref() {
+            Some("smtp") | None => {}
+            Some("mock") => return MailQueue::mock(),
+            Some(backend) => panic!("email.backend has to be \"smtp\" or \"mock\" but is \"{}\"", backend)
+        }
+        let max_retries = match crate::load_config() {
+            Ok(config) => {
+                check_smtp_port(&config);
+                match config.get_int("email.max_retries") {
+                    Ok(x) if x >= 0 => x as u32,
+                    _ => DEFAULT_MAX_RETRIES
+                }
+            }
+            Err(_) => DEFAULT_MAX_RETRIES
+        };
+        MailQueue::start(max_retries, connection)
+    }
+
+    /// Queue a mail. Returns immediately, the mail is sent in the background. The templates get the time of
+    /// sending as `sent_at` in the timezone of the user. The subject is configured for the template in
+    /// `[email_subjects]`, otherwise `subject` is the key of the subject in `translations/<locale>/mail.toml` and the
+    /// locale of the user is used. Mails that are not transactional are
+    /// skipped if the user has unsubscribed from the template and otherwise get an `unsubscribe_token`
+    pub fn sendmail(&self, user: Option<&User>, email: &str, mut context: Context, template: String, subject: String, attachments: Option<Vec<AttachedFile>>, connection: &Connection) -> Result<(), String> {
+        if let Some(user_id) = user.and_then(|u| u.id).filter(|_| !is_transactional(&template)) {
+            if EmailPreference::is_unsubscribed(user_id, &template, connection) {
+                info!("[{}] User {} has unsubscribed from {} mails. The mail is not sent", request_id::current(), user_id, template);
+                return Ok(());
+            }
+            if let Some(token) = crate::load_config().ok().and_then(|config| unsubscribe_token(user_id, &template, &config)) {
+                context.insert("unsubscribe_token", &token);
+            }
+        }
+        context.insert("sent_at", &format_sent_at(user.and_then(|u| u.timezone.as_deref())));
+        let subject = translations::mail_subject(user.map(|u| u.locale.as_str()).unwrap_or(translations::DEFAULT_LOCALE), &template, &subject);
+        // the mail is sent on another thread, so the id of the current request is passed along
+        let job = EmailJob { user_id: user.and_then(|u| u.id), email: email.to_string(), context, template, subject, attachments, request_id: request_id::current() };
+        match &self.backend {
+            MailBackend::Smtp(sender) => {
+                self.drain.pending.fetch_add(1, Ordering::SeqCst);
+                sender.send(job).map_err(|_| {
+                    self.drain.pending.fetch_sub(1, Ordering::SeqCst);
+                    "Mail queue is closed".to_string()
+                })
+            }
+            MailBackend::Mock(mock) => {
+                mock.send(job);
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Templates of mails that are needed for using the account. They are sent regardless of the email preferences
+pub const TRANSACTIONAL_TEMPLATES: [&str; 4] = ["createUser", "resetPassword", "confirmEmail", "invitation"];
+
+/// Whether the mails of a template are sent regardless of the email preferences of the user
+pub fn is_transactional(template: &str) -> bool {
+    TRANSACTIONAL_TEMPLATES.contains(&template)
+}
+
+/// MAC of an unsubscribe token over the user id and the template
+fn unsubscribe_mac(user_id: i32, template: &str, secretkey: &str) -> Hmac<Sha256> {
+    let mut mac: Hmac<Sha256> = Hmac::new_varkey(secretkey.as_bytes()).unwrap();
+    mac.update(format!("{}.{}", user_id, template).as_bytes());
+    mac
+}
+
+/// Token for the unsubscribe link of a mail: `<user id>.<template>.<HMAC-SHA256 with the secret key>`.
+/// Is `None` if no secret key is configured
+pub fn unsubscribe_token(user_id: i32, template: &str, config: &config::Config) -> Option<String> {
+    let secretkey = config.get_str("secretkey").ok()?;
+    let signature = unsubscribe_mac(user_id, template, &secretkey).finalize().into_bytes();
+    Some(format!("{}.{}.{}", user_id, template, base64::encode_config(&signature, base64::URL_SAFE_NO_PAD)))
+}
+
+/// Verify an unsubscribe token and return the user id and the template
+pub fn read_unsubscribe_token(token: &str, config: &config::Config) -> Option<(i32, String)> {
+    let secretkey = config.get_str("secretkey").ok()?;
+    let mut parts = token.splitn(3, '.');
+    let user_id = parts.next()?.parse::<i32>().ok()?;
+    let template = parts.next()?.to_string();
+    let signature = base64::decode_config(parts.next()?, base64::URL_SAFE_NO_PAD).ok()?;
+    // the comparison of the MAC takes constant time
+    unsubscribe_mac(user_id, &template, &secretkey).verify(&signature).ok()?;
+    Some((user_id, template))
+}
+
+/// Check `email.smtp_port` at startup, so a wrong port is noticed before the first mail fails
+fn check_smtp_port(config: &config::Config) {
+    let port = match config.get_int("email.smtp_port") {
+        Ok(port) => port,
+        Err(_) => return
+    };
+    assert!((1..=65535).contains(&port), "email.smtp_port has to be between 1 and 65535 but is {}", port);
+    // 465 expects TLS from the start, STARTTLS upgrades a plain connection
+    if port == 465 && config.get_str("email.smtp_tls_mode").map(|mode| mode == "starttls").unwrap_or(false) {
+        warn!("email.smtp_port is 465 but email.smtp_tls_mode is \"starttls\". Port 465 usually requires \"wrapper\"");
+    }
+}
+
+/// Format of the `sent_at` timestamp in the mail templates
+const SENT_AT_FORMAT: &str = "%Y-%m-%d %H:%M %Z";
+
+/// The current time in the given IANA timezone. Mails to users without a valid timezone use UTC
+fn format_sent_at(timezone: Option<&str>) -> String {
+    let now = Utc::now();
+    match timezone.and_then(|timezone| timezone.parse::<Tz>().ok()) {
+        Some(timezone) => now.with_timezone(&timezone).format(SENT_AT_FORMAT).to_string(),
+        None => now.format(SENT_AT_FORMAT).to_string()
+    }
+}
+
+/// Check whether a mail template with this name exists in `templates_mail`
+pub fn template_exists(template: &str) -> bool {
+    // only plain names, so the name can not point outside of the directory
+    template.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+        && env::current_dir().unwrap().join("templates_mail").join(format!("{}.html.tera", template)).is_file()
+}
+
+/// Render the html and the plain text of a mail template
+pub fn render(template: &str, context: &Context) -> Result<(String, String), String> {
+    let project_root = env::current_dir().unwrap();
+    let templates = format!("{}/templates_mail/*.tera", project_root.to_str().unwrap());
+    // errors are returned instead of panicking because a panic would stop the mail queue
+    let tera = Tera::new(&templates).map_err(|e| e.to_string())?;
+
+    let text = tera.render(&format!("{}.html.tera", template), context).map_err(|e| e.to_string())?;
+    // use the plain text template if there is one. Otherwise the text is generated from the html
+    let plain_text = match tera.render(&format!("{}.txt.tera", template), context) {
+        Ok(plain_text) => plain_text,
+        Err(_) => html2text::from_read(text.as_bytes(), 80)
+    };
+    Ok((text, plain_text))
+}
+
 /// Send a mail
-pub fn sendmail(user: &User, context: Context, template: String, subject: String, attachments: Option<Vec<AttachedFile>>) -> Result<SmtpResult, String> {
+fn deliver(job: &EmailJob) -> Result<SmtpResult, String> {
     let mut smtp_settings: SmtpCredentials = { Default::default() };
-    let mut settings = config::Config::default();
-    let full_configuration = match settings.merge(config::File::with_name("Config")) {
+    let full_configuration = match crate::load_config() {
         Ok(config) => { config }
         Err(_) => { return Err("Configuration file not found".to_string()); }
     };
@@ -62,50 +379,76 @@ pub fn sendmail(user: &User, context: Context, template: String, subject: String
         smtp_settings.password = configuration.get("smtp_password").cloned().unwrap().into_str().unwrap().clone();
         smtp_settings.hostname = configuration.get("smtp_hostname").cloned().unwrap().into_str().unwrap().clone();
         smtp_settings.port = configuration.get("smtp_port").cloned().unwrap().into_int().unwrap().clone() as i32;
+        if !(1..=65535).contains(&smtp_settings.port) {
+            return Err(format!("smtp_port has to be between 1 and 65535 but is {}", smtp_settings.port));
+        }
         smtp_settings.sending_address = configuration.get("smtp_sending_address").cloned().unwrap().into_str().unwrap().clone();
+        smtp_settings.from_name = match configuration.get("smtp_from_name").cloned() {
+            Some(name) => Some(name.into_str().map_err(|e| e.to_string())?).filter(|name| !name.is_empty()),
+            None => None
+        };
+        // implicit TLS is used if no mode is configured
+        smtp_settings.tls_mode = match configuration.get("smtp_tls_mode").cloned() {
+            Some(mode) => mode.into_str().map_err(|e| e.to_string())?,
+            None => "wrapper".to_string()
+        };
     } else {
         return Err("Could not find Configuration in Config.toml".to_string());
     }
 
-    let project_root = env::current_dir().unwrap();
-    let templates = format!("{}/templates_mail/*.tera", project_root.to_str().unwrap());
-    let tera = Tera::new(&templates);
-
-    let text = tera.unwrap().render(&(template + ".html.tera"), &context).unwrap();
-
-    let mut email = Email::builder()
-        .to(user.email.as_ref())
-        .from(smtp_settings.sending_address)
-        .subject(subject)
-        .html(text);
-    if attachments.is_some() {
-        for attachment in attachments.unwrap() {
-           email = email.attachment(attachment.body.as_ref(), attachment.filename.as_ref(), attachment.content_type.borrow()).unwrap()
+    let (text, plain_text) = render(&job.template, &job.context)?;
+
+    let email = Email::builder().to(job.email.as_ref());
+    // lettre takes the address first and the display name second
+    let email = match smtp_settings.from_name {
+        Some(from_name) => email.from((smtp_settings.sending_address, from_name)),
+        None => email.from(smtp_settings.sending_address)
+    };
+    let mut email = email
+        .subject(job.subject.clone())
+        .alternative(text, plain_text);
+    if let Some(attachments) = &job.attachments {
+        for attachment in attachments {
+           email = email.attachment(attachment.body.as_ref(), attachment.filename.as_ref(), attachment.content_type.borrow()).map_err(|e| e.to_string())?
         }
     }
 
     let finished_email = email
         .build()
-        .unwrap();
+        .map_err(|e| e.to_string())?;
 
     let mut tls_builder = TlsConnector::builder();
     tls_builder.min_protocol_version(Some(Protocol::Tlsv10));
     let tls_parameters =
         ClientTlsParameters::new(
             smtp_settings.hostname.clone(),
-            tls_builder.build().unwrap(),
+            tls_builder.build().map_err(|e| e.to_string())?,
         );
 
 
-    let mut mailer = SmtpClient::new(
-        (smtp_settings.hostname.as_str(), 465), ClientSecurity::Wrapper(tls_parameters),
-    ).unwrap()
-        .authentication_mechanism(Mechanism::Login)
-        .credentials(Credentials::new(
-            smtp_settings.username, smtp_settings.password,
-        ))
-        .connection_reuse(ConnectionReuseParameters::ReuseUnlimited)
-        .transport();
+    let client_security = match smtp_settings.tls_mode.as_str() {
+        // implicit TLS, usually port 465
+        "wrapper" => ClientSecurity::Wrapper(tls_parameters),
+        // STARTTLS, usually port 587
+        "starttls" => ClientSecurity::Required(tls_parameters),
+        // no encryption. Only for local development mail servers like Mailhog or Mailpit
+        "none" => ClientSecurity::None,
+        mode => return Err(format!("Unknown smtp_tls_mode \"{}\". Use \"wrapper\", \"starttls\" or \"none\"", mode)),
+    };
+
+    let mut client = SmtpClient::new(
+        (smtp_settings.hostname.as_str(), smtp_settings.port as u16), client_security,
+    ).map_err(|e| e.to_string())?
+        .connection_reuse(ConnectionReuseParameters::ReuseUnlimited);
+    // local development mail servers usually need no authentication
+    if !smtp_settings.username.is_empty() {
+        client = client
+            .authentication_mechanism(Mechanism::Login)
+            .credentials(Credentials::new(
+                smtp_settings.username, smtp_settings.password,
+            ));
+    }
+    let mut mailer = client.transport();
 
     let result = mailer.send(finished_email.into());
 
@@ -0,0 +1,153 @@
+//! Prometheus metrics of the requests, the database pool and the mail queue. `GET /metrics` is only answered for
+//! clients on the loopback interface or with the bearer token from `metrics.token`
+use std::time::Instant;
+
+use prometheus::{Encoder, HistogramOpts, HistogramVec, IntCounter, IntCounterVec, IntGauge, Opts, Registry, TextEncoder};
+use rocket::{Data, Request, Response, State};
+use rocket::fairing::{Fairing, Info, Kind};
+use rocket::http::Status;
+use rocket::request::{self, FromRequest};
+use rocket::Outcome;
+use rocket::response::content;
+use rocket_contrib::json::{Json, JsonValue};
+
+use crate::{CustomResponder, DbConnPool, ErrorCode};
+use crate::mailer::MailQueue;
+
+/// Attach the fairing that records the requests and mount the route for scraping the metrics
+pub fn mount(rocket: rocket::Rocket) -> rocket::Rocket {
+    let collected = Metrics::from_config();
+    rocket
+        .manage(collected.clone())
+        .attach(MetricsFairing(collected))
+        .mount("/", routes![metrics, metrics_error])
+}
+
+/// The collected metrics. Each Rocket instance has its own registry. Is stored in Rocket's managed state
+#[derive(Clone)]
+pub struct Metrics {
+    registry: Registry,
+    requests_total: IntCounterVec,
+    request_duration_seconds: HistogramVec,
+    db_pool_available_connections: IntGauge,
+    email_queue_depth: IntGauge,
+    auth_failures_total: IntCounter,
+    /// Bearer token that allows scraping from other hosts than localhost
+    token: Option<String>,
+}
+
+impl Metrics {
+    /// Register the metrics and read `metrics.token` from the configuration
+    pub fn from_config() -> Metrics {
+        let token = crate::load_config().ok()
+            .and_then(|c| c.get_str("metrics.token").ok())
+            .filter(|token| !token.is_empty());
+        let requests_total = IntCounterVec::new(Opts::new("http_requests_total", "Number of handled HTTP requests"), &["method", "route", "status"]).unwrap();
+        let request_duration_seconds = HistogramVec::new(HistogramOpts::new("http_request_duration_seconds", "Duration of HTTP requests in seconds"), &["method", "route"]).unwrap();
+        let db_pool_available_connections = IntGauge::new("db_pool_available_connections", "Idle connections in the database pool").unwrap();
+        let email_queue_depth = IntGauge::new("email_queue_depth", "Mails that are queued or being sent").unwrap();
+        let auth_failures_total = IntCounter::new("auth_failures_total", "Number of responses with 401 Unauthorized").unwrap();
+        let registry = Registry::new();
+        registry.register(Box::new(requests_total.clone())).unwrap();
+        registry.register(Box::new(request_duration_seconds.clone())).unwrap();
+        registry.register(Box::new(db_pool_available_connections.clone())).unwrap();
+        registry.register(Box::new(email_queue_depth.clone())).unwrap();
+        registry.register(Box::new(auth_failures_total.clone())).unwrap();
+        Metrics { registry, requests_total, request_duration_seconds, db_pool_available_connections, email_queue_depth, auth_failures_total, token }
+    }
+}
+
+/// Time at which the request was received
+struct RequestStart(Instant);
+
+/// Fairing that counts the requests and records their duration per route
+pub struct MetricsFairing(Metrics);
+
+impl Fairing for MetricsFairing {
+    fn info(&self) -> Info {
+        Info {
+            name: "Metrics",
+            kind: Kind::Request | Kind::Response,
+        }
+    }
+
+    fn on_request(&self, request: &mut Request, _data: &Data) {
+        request.local_cache(|| RequestStart(Instant::now()));
+    }
+
+    fn on_response(&self, request: &Request, response: &mut Response) {
+        let started_at = request.local_cache(|| RequestStart(Instant::now())).0;
+        // the route pattern instead of the path, so ids do not create a time series per user
+        let route = match request.route() {
+            Some(route) => route.uri.path().to_string(),
+            None => "unmatched".to_string()
+        };
+        let method = request.method().as_str();
+        let status = response.status().code.to_string();
+        self.0.requests_total.with_label_values(&[method, &route, &status]).inc();
+        self.0.request_duration_seconds.with_label_values(&[method, &route]).observe(started_at.elapsed().as_secs_f64());
+        if response.status() == Status::Unauthorized {
+            self.0.auth_failures_total.inc();
+        }
+    }
+}
+
+/// Request guard for scraping the metrics. Succeeds for clients on the loopback interface or with
+/// `Authorization: Bearer <metrics.token>`. The address of the connection is checked instead of `client_ip()`,
+/// because that prefers the `X-Real-IP` header which every client can send
+pub struct MetricsAccess;
+
+impl<'a, 'r> FromRequest<'a, 'r> for MetricsAccess {
+    type Error = ();
+    fn from_request(request: &'a Request<'r>) -> request::Outcome<MetricsAccess, ()> {
+        if request.remote().map(|remote| remote.ip().is_loopback()).unwrap_or(false) {
+            return Outcome::Success(MetricsAccess);
+        }
+        let metrics = match request.guard::<State<Metrics>>().succeeded() {
+            Some(metrics) => metrics,
+            None => return Outcome::Forward(())
+        };
+        let bearer = request.headers().get_one("Authorization").and_then(|header| header.strip_prefix("Bearer "));
+        match (&metrics.token, bearer) {
+            (Some(token), Some(bearer)) if token == bearer => Outcome::Success(MetricsAccess),
+            _ => Outcome::Forward(())
+        }
+    }
+}
+
+/// Metrics in the Prometheus text format. The gauges are read when the metrics are scraped
+///
+/// # Arguments
+///
+/// * `_access` - A client on localhost or with the metrics token
+/// * `metrics` - The collected metrics
+/// * `pool` - The database pool
+/// * `mail_queue` - The mail queue
+///
+/// # Example
+///
+/// ```text
+/// curl --request GET \
+///   --url http://localhost:8000/metrics \
+///   --header 'authorization: Bearer 5d0c4f0b2ce1e8a7'
+/// ```
+///
+#[get("/metrics")]
+fn metrics(_access: MetricsAccess, metrics: State<Metrics>, pool: State<DbConnPool>, mail_queue: State<MailQueue>) -> Result<content::Plain<String>, CustomResponder> {
+    metrics.db_pool_available_connections.set(pool.0.state().idle_connections as i64);
+    metrics.email_queue_depth.set(mail_queue.drain_handle().pending() as i64);
+    let mut buffer = Vec::new();
+    match TextEncoder::new().encode(&metrics.registry.gather(), &mut buffer) {
+        Ok(_) => Ok(content::Plain(String::from_utf8_lossy(&buffer).to_string())),
+        Err(e) => {
+            error!("[{}] Metrics could not be encoded: {}", crate::request_id::current(), e);
+            Err(CustomResponder::InternalServerError(Json(json!({"status": {"code": 500, "text": "Metrics could not be encoded", "error_code": ErrorCode::MetricsError}}))))
+        }
+    }
+}
+
+/// Error route for the metrics. Is executed when the client is neither on localhost nor has the metrics token
+#[get("/metrics", rank = 999)]
+fn metrics_error() -> Result<Json<JsonValue>, CustomResponder> {
+    Err(CustomResponder::Unauthorized(Json(json!({"status": {"code": 401,"text": "Not authorized", "error_code": ErrorCode::NotAuthorized}}))))
+}
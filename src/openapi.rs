@@ -0,0 +1,184 @@
+//! OpenAPI 3.0 description of the API. Every module describes its routes next to its `mount` function,
+//! the schemas of the request bodies are derived with `schemars`
+use rocket_contrib::json::Json;
+use rocket_contrib::templates::Template;
+use rocket_contrib::templates::tera::Context;
+use schemars::gen::{SchemaGenerator, SchemaSettings};
+use schemars::schema::Schema;
+use schemars::JsonSchema;
+use serde_json::{Map, Value};
+
+use crate::{CustomResponder, ErrorCode};
+use crate::security_headers::CspNonce;
+
+/// Mount routes for Rocket.
+pub fn mount(rocket: rocket::Rocket) -> rocket::Rocket {
+    rocket.mount("/", routes![openapi_json, docs])
+}
+
+/// Who is allowed to call a route
+pub enum Access {
+    /// No authentication needed
+    Public,
+    /// A logged in user. Authenticated with a token or an api key
+    User,
+    /// A logged in admin user
+    Admin,
+}
+
+/// Description of a route for the OpenAPI document
+pub struct RouteDoc {
+    /// HTTP method in lower case
+    pub method: &'static str,
+    /// Full path with parameters in braces, e.g. `/user/sessions/{jti}`
+    pub path: &'static str,
+    /// Short description of the route
+    pub summary: &'static str,
+    /// Who is allowed to call the route
+    pub access: Access,
+    /// Names of the optional query parameters
+    pub query: &'static [&'static str],
+    /// Schema of the JSON request body
+    pub body: Option<Schema>,
+    /// Whether the route responds with the JSON envelope of `data` and `status`
+    pub json_response: bool,
+}
+
+impl RouteDoc {
+    /// Describe a route without request body that responds with the JSON envelope
+    pub fn new(method: &'static str, path: &'static str, summary: &'static str, access: Access) -> RouteDoc {
+        RouteDoc { method, path, summary, access, query: &[], body: None, json_response: true }
+    }
+}
+
+/// Reference the schema of a request body. The schema itself is added to the components of the document
+pub fn body<T: JsonSchema>(generator: &mut SchemaGenerator) -> Option<Schema> {
+    Some(generator.subschema_for::<T>())
+}
+
+/// Status of every JSON response
+#[derive(JsonSchema)]
+#[allow(dead_code)]
+struct ApiStatus {
+    /// HTTP status code
+    code: u16,
+    /// Human readable description
+    text: String,
+    /// Machine readable error code. Only present for errors
+    error_code: Option<ErrorCode>,
+}
+
+/// Envelope of every JSON response
+#[derive(JsonSchema)]
+#[allow(dead_code)]
+struct ApiResponse {
+    /// Payload of the response. Its content depends on the route
+    data: Option<Value>,
+    status: ApiStatus,
+}
+
+/// Build the OpenAPI operation for a route
+fn operation(route: &RouteDoc, response: &Schema) -> Value {
+    // parameters in braces are path parameters
+    let mut parameters: Vec<Value> = route.path.split('/')
+        .filter(|segment| segment.starts_with('{') && segment.ends_with('}'))
+        .map(|segment| serde_json::json!({"name": &segment[1..segment.len() - 1], "in": "path", "required": true, "schema": {"type": "string"}}))
+        .collect();
+    parameters.extend(route.query.iter().map(|name| serde_json::json!({"name": name, "in": "query", "required": false, "schema": {"type": "string"}})));
+
+    let mut responses = Map::new();
+    if route.json_response {
+        responses.insert("200".to_string(), serde_json::json!({"description": "Successful response", "content": {"application/json": {"schema": response}}}));
+        responses.insert("default".to_string(), serde_json::json!({"description": "Error with status and error code", "content": {"application/json": {"schema": response}}}));
+    } else {
+        responses.insert("200".to_string(), serde_json::json!({"description": "Successful response"}));
+    }
+    if let Access::User | Access::Admin = route.access {
+        responses.insert("401".to_string(), serde_json::json!({"description": "Not authorized"}));
+    }
+
+    let mut operation = serde_json::json!({
+        "summary": route.summary,
+        "parameters": parameters,
+        "responses": responses,
+    });
+    match route.access {
+        Access::Public => {}
+        Access::User => {
+            operation["security"] = serde_json::json!([{"bearer": []}, {"cookie": []}, {"api_key": []}]);
+        }
+        Access::Admin => {
+            operation["security"] = serde_json::json!([{"bearer": []}, {"cookie": []}, {"api_key": []}]);
+            operation["description"] = Value::String("Only for admin users".to_string());
+        }
+    }
+    if let Some(body) = &route.body {
+        operation["requestBody"] = serde_json::json!({"required": true, "content": {"application/json": {"schema": body}}});
+    }
+    operation
+}
+
+/// Build the OpenAPI document of all routes
+pub fn spec() -> Result<::openapi::v3_0::Spec, String> {
+    let mut generator = SchemaSettings::openapi3().into_generator();
+    let response = generator.subschema_for::<ApiResponse>();
+    let mut routes = crate::user::openapi(&mut generator);
+    routes.extend(crate::admin::openapi(&mut generator));
+    routes.extend(crate::health::openapi());
+    routes.extend(crate::webhook::openapi(&mut generator));
+    routes.extend(crate::notifications::openapi());
+    routes.extend(crate::feedback::openapi(&mut generator));
+
+    let mut paths = Map::new();
+    for route in routes.iter() {
+        let path = paths.entry(route.path.to_string()).or_insert_with(|| Value::Object(Map::new()));
+        path[route.method] = operation(route, &response);
+    }
+    let document = serde_json::json!({
+        "openapi": "3.0.3",
+        "info": {
+            "title": env!("CARGO_PKG_NAME"),
+            "description": env!("CARGO_PKG_DESCRIPTION"),
+            "version": env!("CARGO_PKG_VERSION"),
+        },
+        "paths": paths,
+        "components": {
+            "schemas": generator.take_definitions(),
+            "securitySchemes": {
+                "bearer": {"type": "http", "scheme": "bearer", "bearerFormat": "JWT"},
+                "cookie": {"type": "apiKey", "in": "cookie", "name": "token"},
+                "api_key": {"type": "apiKey", "in": "header", "name": "X-Api-Key"},
+            },
+        },
+    });
+    serde_json::from_value(document).map_err(|e| e.to_string())
+}
+
+/// The OpenAPI document of the API
+///
+/// # Example
+///
+/// ```text
+/// curl --request GET \
+///   --url http://localhost:8000/openapi.json
+/// ```
+///
+#[get("/openapi.json")]
+fn openapi_json() -> Result<Json<::openapi::v3_0::Spec>, CustomResponder> {
+    match spec() {
+        Ok(spec) => Ok(Json(spec)),
+        Err(e) => {
+            error!("OpenAPI document could not be built: {}", e);
+            Err(CustomResponder::InternalServerError(Json(json!({"status": {"code": 500, "text": "OpenAPI document could not be built"}}))))
+        }
+    }
+}
+
+/// Swagger UI for the OpenAPI document
+#[get("/docs")]
+fn docs(nonce: CspNonce) -> Template {
+    let mut context = Context::new();
+    context.insert("spec_url", "/openapi.json");
+    context.insert("csp_nonce", &nonce.0);
+    Template::render("docs", &context)
+}
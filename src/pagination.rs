@@ -0,0 +1,114 @@
+//! Shared pagination for list routes. Routes read the `page` and `per_page` query parameters with the
+//! `PaginationParams` guard and respond with a `PaginatedResponse`. Routes for large tables can additionally
+//! accept a `cursor` and respond with a `CursorPage`, which is loaded with `WHERE id > cursor` instead of an offset
+use rocket::{Outcome, Request};
+use rocket::request::{self, FromRequest};
+use serde::Serialize;
+
+/// Number of items per page if `per_page` is missing
+const DEFAULT_PER_PAGE: u32 = 20;
+/// Maximum number of items per page if `pagination.max_per_page` is not configured
+const DEFAULT_MAX_PER_PAGE: u32 = 100;
+
+/// Page of a list route. Read from the query parameters `page` (starting at 0) and `per_page`.
+/// Missing or invalid values fall back to the defaults, `per_page` is capped at `pagination.max_per_page`
+#[derive(Debug, Clone, Copy)]
+pub struct PaginationParams {
+    pub page: u32,
+    pub per_page: u32,
+}
+
+impl PaginationParams {
+    /// Number of items before the page
+    pub fn offset(&self) -> i64 {
+        self.page as i64 * self.per_page as i64
+    }
+
+    /// Maximum number of items of the page
+    pub fn limit(&self) -> i64 {
+        self.per_page as i64
+    }
+}
+
+impl<'a, 'r> FromRequest<'a, 'r> for PaginationParams {
+    type Error = ();
+
+    fn from_request(request: &'a Request<'r>) -> request::Outcome<PaginationParams, ()> {
+        let max_per_page = match crate::load_config() {
+            Ok(config) => match config.get_int("pagination.max_per_page") {
+                Ok(x) if x > 0 => x as u32,
+                _ => DEFAULT_MAX_PER_PAGE
+            },
+            Err(_) => DEFAULT_MAX_PER_PAGE
+        };
+        let page = request.get_query_value::<u32>("page").and_then(Result::ok).unwrap_or(0);
+        let per_page = request.get_query_value::<u32>("per_page").and_then(Result::ok).unwrap_or(DEFAULT_PER_PAGE);
+        Outcome::Success(PaginationParams { page, per_page: per_page.max(1).min(max_per_page) })
+    }
+}
+
+/// One page of a list
+#[derive(Serialize, Debug)]
+pub struct PaginatedResponse<T: Serialize> {
+    /// Items of the page
+    pub items: Vec<T>,
+    /// Number of items of all pages
+    pub total_count: i64,
+    pub page: u32,
+    pub per_page: u32,
+    /// Whether there is another page after this one
+    pub has_next: bool,
+}
+
+impl<T: Serialize> PaginatedResponse<T> {
+    /// Wrap a page that has been loaded from the database
+    pub fn new(items: Vec<T>, total_count: i64, params: &PaginationParams) -> PaginatedResponse<T> {
+        PaginatedResponse {
+            items,
+            total_count,
+            page: params.page,
+            per_page: params.per_page,
+            has_next: params.offset() + params.limit() < total_count,
+        }
+    }
+
+    /// Cut the page out of a complete list. For lists that are filtered after loading them from the database
+    pub fn from_all(items: Vec<T>, params: &PaginationParams) -> PaginatedResponse<T> {
+        let total_count = items.len() as i64;
+        let page = items.into_iter().skip(params.offset() as usize).take(params.limit() as usize).collect();
+        PaginatedResponse::new(page, total_count, params)
+    }
+}
+
+/// Cursor for the page after the item with the given id. The cursor is opaque for clients
+pub fn encode_cursor(last_seen_id: i32) -> String {
+    base64::encode_config(last_seen_id.to_string(), base64::URL_SAFE_NO_PAD)
+}
+
+/// Id of the last item of the previous page. Returns `None` for an invalid cursor
+pub fn decode_cursor(cursor: &str) -> Option<i32> {
+    let decoded = base64::decode_config(cursor, base64::URL_SAFE_NO_PAD).ok()?;
+    String::from_utf8(decoded).ok()?.parse().ok()
+}
+
+/// One page of a list with cursor pagination
+#[derive(Serialize, Debug)]
+pub struct CursorPage<T: Serialize> {
+    /// Items of the page
+    pub items: Vec<T>,
+    /// Cursor for the next page. Missing on the last page
+    pub next_cursor: Option<String>,
+    /// Whether there is another page after this one
+    pub has_more: bool,
+}
+
+impl<T: Serialize> CursorPage<T> {
+    /// Wrap a page that has been loaded with one item more than `limit`, so it is known whether there are more
+    /// items. `id` returns the id of an item for the next cursor
+    pub fn new<F: Fn(&T) -> i32>(mut items: Vec<T>, limit: i64, id: F) -> CursorPage<T> {
+        let has_more = items.len() as i64 > limit;
+        items.truncate(limit as usize);
+        let next_cursor = if has_more { items.last().map(|item| encode_cursor(id(item))) } else { None };
+        CursorPage { items, next_cursor, has_more }
+    }
+}
@@ -0,0 +1,148 @@
+//! Rate limiting of the authentication routes. The counters are stored in Redis, so they survive restarts and are
+//! shared by all instances. If Redis is not available, requests are let through
+use std::sync::Mutex;
+use std::time::Duration;
+
+use redis::Commands;
+use rocket::{Data, Request};
+use rocket::fairing::{Fairing, Info, Kind};
+use rocket::http::Method;
+use rocket::http::uri::Origin;
+use rocket_contrib::json::Json;
+
+use crate::{CustomResponder, ErrorCode, TrustedProxies};
+
+/// Length of the rate limiting window if `rate_limit.window_seconds` is not configured
+const DEFAULT_WINDOW_SECONDS: usize = 60;
+/// Routes that are rate limited with their configuration key and the number of requests per window if it is not configured
+const LIMITED_ROUTES: [(&str, &str, i64); 3] = [
+    ("/user/login", "rate_limit.login", 10),
+    ("/user/request_reset", "rate_limit.request_reset", 5),
+    ("/user/resend_activation", "rate_limit.resend_activation", 5),
+];
+/// Path of the route that answers requests that exceed the limit
+const TOO_MANY_REQUESTS_PATH: &str = "/too_many_requests";
+/// Redis is asked on every limited request, so a slow server must not block the login
+const REDIS_TIMEOUT: Duration = Duration::from_millis(200);
+
+/// Attach the fairing and mount the route for limited requests if `[redis] url` is configured
+pub fn mount(rocket: rocket::Rocket) -> rocket::Rocket {
+    let config = crate::load_config().unwrap_or_default();
+    match config.get_str("redis.url") {
+        Ok(url) => {
+            let client = match redis::Client::open(url.as_str()) {
+                Ok(client) => client,
+                Err(e) => panic!("redis.url is not a valid Redis url: {}", e)
+            };
+            rocket
+                .attach(RateLimitFairing::from_config(client, &config))
+                .mount("/", routes![too_many_requests])
+        }
+        Err(_) => {
+            warn!("No [redis] url configured. Authentication routes are not rate limited");
+            rocket
+        }
+    }
+}
+
+/// Fairing that counts the requests per client ip and route in Redis and redirects requests that exceed the
+/// limit of the route to an error route
+pub struct RateLimitFairing {
+    client: redis::Client,
+    /// Connection that is reused for all requests. Is opened again after an error
+    connection: Mutex<Option<redis::Connection>>,
+    window_seconds: usize,
+    /// Path and maximum number of requests per window
+    limits: Vec<(&'static str, i64)>,
+    /// Reverse proxies whose `X-Real-IP` header is used as the client ip, like for the `ClientIp` guard
+    trusted_proxies: TrustedProxies,
+}
+
+impl RateLimitFairing {
+    /// Read the `[rate_limit]` settings from the configuration. Panics on invalid values
+    fn from_config(client: redis::Client, config: &config::Config) -> RateLimitFairing {
+        let window_seconds = match config.get_int("rate_limit.window_seconds") {
+            Ok(seconds) if seconds > 0 => seconds as usize,
+            Ok(seconds) => panic!("rate_limit.window_seconds has to be positive but is {}", seconds),
+            Err(_) => DEFAULT_WINDOW_SECONDS
+        };
+        let limits = LIMITED_ROUTES.iter().map(|(path, key, default)| {
+            match config.get_int(key) {
+                Ok(limit) if limit > 0 => (*path, limit),
+                Ok(limit) => panic!("{} has to be positive but is {}", key, limit),
+                Err(_) => (*path, *default)
+            }
+        }).collect();
+        RateLimitFairing { client, connection: Mutex::new(None), window_seconds, limits, trusted_proxies: TrustedProxies::from_config() }
+    }
+
+    /// Increment the counter of the client for the route. Returns the new count or an error if Redis is not available
+    fn increment(&self, key: &str) -> redis::RedisResult<i64> {
+        let mut connection = self.connection.lock().unwrap();
+        if connection.is_none() {
+            let new_connection = self.client.get_connection_with_timeout(REDIS_TIMEOUT)?;
+            new_connection.set_read_timeout(Some(REDIS_TIMEOUT))?;
+            new_connection.set_write_timeout(Some(REDIS_TIMEOUT))?;
+            *connection = Some(new_connection);
+        }
+        let result = count(connection.as_mut().unwrap(), key, self.window_seconds);
+        // a broken connection is opened again for the next request
+        if result.is_err() {
+            *connection = None;
+        }
+        result
+    }
+}
+
+/// Increment a counter that expires after the window
+fn count(connection: &mut redis::Connection, key: &str, window_seconds: usize) -> redis::RedisResult<i64> {
+    let count: i64 = connection.incr(key, 1)?;
+    // the window starts with the first request
+    if count == 1 {
+        let _: () = connection.expire(key, window_seconds)?;
+    }
+    Ok(count)
+}
+
+impl Fairing for RateLimitFairing {
+    fn info(&self) -> Info {
+        Info {
+            name: "Rate limit",
+            kind: Kind::Request,
+        }
+    }
+
+    fn on_request(&self, request: &mut Request, _data: &Data) {
+        // preflight requests of browsers are not counted
+        if request.method() == Method::Options {
+            return;
+        }
+        let path = request.uri().path().to_string();
+        let limit = match self.limits.iter().find(|(limited, _)| *limited == path) {
+            Some((_, limit)) => *limit,
+            None => return
+        };
+        let ip = match self.trusted_proxies.client_ip(request) {
+            Some(ip) => ip.to_string(),
+            None => return
+        };
+        let key = format!("ratelimit:{}:{}", ip, path);
+        match self.increment(&key) {
+            Ok(count) if count > limit => {
+                warn!("[{}] Rate limit of {} exceeded by {}", crate::request_id::current(), path, ip);
+                // fairings can not respond themselves, so the request is routed to the error route
+                request.set_method(Method::Get);
+                request.set_uri(Origin::parse(TOO_MANY_REQUESTS_PATH).unwrap());
+            }
+            Ok(_) => {}
+            // fail open: an unavailable Redis must not prevent logins
+            Err(e) => warn!("[{}] Rate limit could not be checked: {}", crate::request_id::current(), e)
+        }
+    }
+}
+
+/// Error route for requests that exceed the rate limit of their route
+#[get("/too_many_requests")]
+fn too_many_requests() -> CustomResponder {
+    CustomResponder::TooManyRequests(Json(json!({"status": {"code": 429,"text": "Too many requests. Please try again later", "error_code": ErrorCode::TooManyRequests}})))
+}
@@ -0,0 +1,70 @@
+//! Correlation ids for requests. Every response carries the id in the `X-Request-ID` header and log lines of the
+//! request contain it
+use std::cell::RefCell;
+
+use rocket::{Data, Outcome, Request, Response};
+use rocket::fairing::{Fairing, Info, Kind};
+use rocket::http::Header;
+use rocket::request::{self, FromRequest};
+use uuid::Uuid;
+
+/// Name of the header that carries the request id
+pub const REQUEST_ID_HEADER: &str = "X-Request-ID";
+/// Maximum length of a request id that is accepted from the client
+const MAX_REQUEST_ID_LENGTH: usize = 128;
+
+thread_local! {
+    /// Id of the request that is handled by the current thread. Rocket handles a request on a single worker thread
+    static CURRENT_REQUEST_ID: RefCell<Option<String>> = RefCell::new(None);
+}
+
+/// Id of the request that is handled by the current thread or `-` outside of a request
+pub fn current() -> String {
+    CURRENT_REQUEST_ID.with(|id| id.borrow().clone()).unwrap_or_else(|| "-".to_string())
+}
+
+/// The id of a request. Is stored in the request local cache
+#[derive(Clone, Debug)]
+pub struct RequestId(pub String);
+
+impl<'a, 'r> FromRequest<'a, 'r> for RequestId {
+    type Error = ();
+
+    fn from_request(request: &'a Request<'r>) -> request::Outcome<RequestId, ()> {
+        Outcome::Success(request.local_cache(|| RequestId(Uuid::new_v4().to_string())).clone())
+    }
+}
+
+/// Only ids that are safe to write into log lines are taken over from the client
+fn is_valid_request_id(id: &str) -> bool {
+    !id.is_empty() && id.len() <= MAX_REQUEST_ID_LENGTH && id.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == '.')
+}
+
+/// Fairing that assigns an id to every request and adds it to the response
+pub struct RequestIdFairing;
+
+impl Fairing for RequestIdFairing {
+    fn info(&self) -> Info {
+        Info {
+            name: "Request ID",
+            kind: Kind::Request | Kind::Response,
+        }
+    }
+
+    fn on_request(&self, request: &mut Request, _data: &Data) {
+        // use the id of the client if there is one, e.g. from a load balancer. Otherwise a new one is created
+        let id = match request.headers().get_one(REQUEST_ID_HEADER) {
+            Some(id) if is_valid_request_id(id) => id.to_string(),
+            _ => Uuid::new_v4().to_string()
+        };
+        let id = request.local_cache(|| RequestId(id)).0.clone();
+        CURRENT_REQUEST_ID.with(|current| *current.borrow_mut() = Some(id));
+    }
+
+    fn on_response(&self, request: &Request, response: &mut Response) {
+        let id = request.local_cache(|| RequestId(Uuid::new_v4().to_string())).0.clone();
+        response.set_header(Header::new(REQUEST_ID_HEADER, id));
+        // the worker thread handles another request next
+        CURRENT_REQUEST_ID.with(|current| *current.borrow_mut() = None);
+    }
+}
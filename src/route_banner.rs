@@ -0,0 +1,30 @@
+//! Log of all mounted routes at startup. Rocket prints its routes with its own logger, which is replaced by
+//! log4rs, so the configured log output would not contain them otherwise
+use rocket::Rocket;
+use rocket::fairing::{Fairing, Info, Kind};
+
+/// Attach the fairing. It has to be attached after all routes are mounted, because it only sees the routes
+/// that are mounted when it is attached
+pub fn mount(rocket: Rocket) -> Rocket {
+    rocket.attach(RouteBannerFairing)
+}
+
+/// Fairing that logs method, path and rank of every mounted route
+pub struct RouteBannerFairing;
+
+impl Fairing for RouteBannerFairing {
+    fn info(&self) -> Info {
+        Info {
+            name: "Route banner",
+            kind: Kind::Attach,
+        }
+    }
+
+    fn on_attach(&self, rocket: Rocket) -> Result<Rocket, Rocket> {
+        info!("Mounted routes:");
+        for route in rocket.routes() {
+            info!("{} {} (rank {})", route.method, route.uri, route.rank);
+        }
+        Ok(rocket)
+    }
+}
@@ -0,0 +1,83 @@
+//! Security related response headers. Every header can be switched off in the `[security_headers]` section,
+//! e.g. to relax the Content Security Policy for a frontend
+use rocket::{Outcome, Request, Response};
+use rocket::fairing::{Fairing, Info, Kind};
+use rocket::http::Header;
+use rocket::request::{self, FromRequest};
+
+/// Headers with their configuration key. `Strict-Transport-Security` is only sent if `server.force_https` is set
+const HEADERS: [(&str, &str, &str); 4] = [
+    ("security_headers.hsts", "Strict-Transport-Security", "max-age=31536000; includeSubDomains"),
+    ("security_headers.frame_options", "X-Frame-Options", "DENY"),
+    ("security_headers.content_type_options", "X-Content-Type-Options", "nosniff"),
+    ("security_headers.referrer_policy", "Referrer-Policy", "strict-origin-when-cross-origin"),
+];
+
+/// Random nonce of a request that is stored in the request's local cache
+struct NonceValue(String);
+
+/// The nonce of the Content Security Policy of the current request. Templates have to put it into the `nonce`
+/// attribute of their `<script>` and `<link>` tags as `csp_nonce`, otherwise the browser does not run them
+pub struct CspNonce(pub String);
+
+/// The nonce of the request. It is generated with the first use, so the template and the header get the same value
+fn nonce(request: &Request) -> String {
+    request.local_cache(|| NonceValue(base64::encode(rand::random::<[u8; 16]>()))).0.clone()
+}
+
+impl<'a, 'r> FromRequest<'a, 'r> for CspNonce {
+    type Error = ();
+    fn from_request(request: &'a Request<'r>) -> request::Outcome<CspNonce, ()> {
+        Outcome::Success(CspNonce(nonce(request)))
+    }
+}
+
+/// Attach the fairing
+pub fn mount(rocket: rocket::Rocket) -> rocket::Rocket {
+    rocket.attach(SecurityHeadersFairing::from_config())
+}
+
+/// Fairing that adds the enabled security headers to every response
+pub struct SecurityHeadersFairing {
+    /// Name and value of the enabled headers
+    headers: Vec<(&'static str, &'static str)>,
+    /// Whether the Content Security Policy with the nonce of the request is sent
+    content_security_policy: bool,
+}
+
+impl SecurityHeadersFairing {
+    /// Read the `[security_headers]` switches from the configuration. All headers are enabled by default
+    pub fn from_config() -> SecurityHeadersFairing {
+        let config = crate::load_config().unwrap_or_default();
+        let force_https = config.get_bool("server.force_https").unwrap_or(false);
+        let headers = HEADERS.iter()
+            .filter(|(key, _, _)| config.get_bool(key).unwrap_or(true))
+            // HSTS would lock out browsers of a server that is only reachable over HTTP
+            .filter(|(_, name, _)| force_https || *name != "Strict-Transport-Security")
+            .map(|(_, name, value)| (*name, *value))
+            .collect();
+        let content_security_policy = config.get_bool("security_headers.content_security_policy").unwrap_or(true);
+        SecurityHeadersFairing { headers, content_security_policy }
+    }
+}
+
+impl Fairing for SecurityHeadersFairing {
+    fn info(&self) -> Info {
+        Info {
+            name: "Security headers",
+            kind: Kind::Response,
+        }
+    }
+
+    fn on_response(&self, request: &Request, response: &mut Response) {
+        for (name, value) in &self.headers {
+            response.set_header(Header::new(*name, *value));
+        }
+        if self.content_security_policy {
+            // scripts and stylesheets of the templates are only allowed with the nonce of the request
+            let nonce = nonce(request);
+            response.set_header(Header::new("Content-Security-Policy", format!(
+                "default-src 'self'; script-src 'nonce-{}' 'strict-dynamic'; style-src 'self' 'nonce-{}'", nonce, nonce)));
+        }
+    }
+}
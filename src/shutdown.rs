@@ -0,0 +1,33 @@
+//! Shutdown on SIGTERM and SIGINT. Rocket 0.4 has no way to stop the server, so the process exits after the
+//! queued mails are sent or `email.shutdown_timeout_seconds` is over. Requests that are still running are aborted
+use std::process;
+use std::time::Duration;
+
+use crate::mailer::MailQueue;
+
+/// Time to wait for queued mails if `email.shutdown_timeout_seconds` is not configured
+const DEFAULT_SHUTDOWN_TIMEOUT_SECONDS: u64 = 10;
+
+/// Install the signal handler. Has to be called after the mail queue is managed by Rocket
+pub fn install(rocket: &rocket::Rocket) {
+    let timeout = match crate::load_config().ok().and_then(|c| c.get_int("email.shutdown_timeout_seconds").ok()) {
+        Some(seconds) if seconds >= 0 => Duration::from_secs(seconds as u64),
+        Some(seconds) => panic!("email.shutdown_timeout_seconds must not be negative but is {}", seconds),
+        None => Duration::from_secs(DEFAULT_SHUTDOWN_TIMEOUT_SECONDS)
+    };
+    let drain = match rocket.state::<MailQueue>() {
+        Some(queue) => queue.drain_handle(),
+        None => return
+    };
+    let result = ctrlc::set_handler(move || {
+        info!("Shutting down. Waiting up to {} seconds for queued mails", timeout.as_secs());
+        match drain.drain(timeout) {
+            0 => info!("All queued mails were sent"),
+            unsent => warn!("{} queued mails were not sent before the shutdown", unsent)
+        }
+        process::exit(0);
+    });
+    if let Err(e) = result {
+        error!("Signal handler could not be installed: {}", e);
+    }
+}
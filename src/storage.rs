@@ -0,0 +1,236 @@
+//! Storage for uploaded files like profile images. Files are stored in a local directory or, with the feature `s3`,
+//! in an S3 bucket. The backend is selected with `storage.backend` in the configuration file
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+use rocket_contrib::serve::StaticFiles;
+
+/// Directory for local files if `storage.directory` is not configured
+const DEFAULT_DIRECTORY: &str = "uploads";
+/// Path under which local files are served if `storage.base_url` is not configured
+const DEFAULT_BASE_URL: &str = "/uploads";
+
+/// Error of a storage backend
+#[derive(Debug)]
+pub enum StorageError {
+    /// There is no file with the key
+    NotFound,
+    /// The key contains characters that are not allowed
+    InvalidKey,
+    /// The backend failed
+    Backend(String),
+}
+
+impl fmt::Display for StorageError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            StorageError::NotFound => write!(f, "File not found"),
+            StorageError::InvalidKey => write!(f, "Invalid key"),
+            StorageError::Backend(e) => write!(f, "Storage backend failed: {}", e),
+        }
+    }
+}
+
+impl From<io::Error> for StorageError {
+    fn from(e: io::Error) -> StorageError {
+        match e.kind() {
+            io::ErrorKind::NotFound => StorageError::NotFound,
+            _ => StorageError::Backend(e.to_string())
+        }
+    }
+}
+
+/// A place to store files. Files are identified by a key like `profile_images/<uuid>.jpg`
+pub trait Storage: Send + Sync {
+    /// Store a file and return the URL under which it is available
+    fn save(&self, key: &str, data: &[u8]) -> Result<String, StorageError>;
+
+    /// Load a file
+    fn load(&self, key: &str) -> Result<Vec<u8>, StorageError>;
+
+    /// Delete a file
+    fn delete(&self, key: &str) -> Result<(), StorageError>;
+
+    /// URL that is prepended to the keys
+    fn base_url(&self) -> &str;
+
+    /// Find the key of a file from the URL that was returned by `save`
+    fn key_from_url(&self, url: &str) -> Option<String> {
+        url.strip_prefix(self.base_url())
+            .map(|key| key.trim_start_matches('/').to_string())
+            .filter(|key| valid_key(key))
+    }
+}
+
+/// Keys may not leave the storage directory
+fn valid_key(key: &str) -> bool {
+    !key.is_empty() && !key.starts_with('/') && key.split('/').all(|segment| !segment.is_empty() && segment != "." && segment != "..")
+}
+
+/// Stores files in a local directory. The files are served by Rocket under the base URL
+pub struct LocalStorage {
+    directory: PathBuf,
+    base_url: String,
+}
+
+impl LocalStorage {
+    pub fn new(directory: PathBuf, base_url: String) -> LocalStorage {
+        LocalStorage { directory, base_url }
+    }
+
+    /// Path of the file with the given key
+    fn path(&self, key: &str) -> Result<PathBuf, StorageError> {
+        if !valid_key(key) {
+            return Err(StorageError::InvalidKey);
+        }
+        Ok(self.directory.join(key))
+    }
+}
+
+impl Storage for LocalStorage {
+    fn save(&self, key: &str, data: &[u8]) -> Result<String, StorageError> {
+        let path = self.path(key)?;
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(path, data)?;
+        Ok(format!("{}/{}", self.base_url.trim_end_matches('/'), key))
+    }
+
+    fn load(&self, key: &str) -> Result<Vec<u8>, StorageError> {
+        Ok(fs::read(self.path(key)?)?)
+    }
+
+    fn delete(&self, key: &str) -> Result<(), StorageError> {
+        Ok(fs::remove_file(self.path(key)?)?)
+    }
+
+    fn base_url(&self) -> &str {
+        &self.base_url
+    }
+}
+
+/// Stores files in an S3 bucket
+#[cfg(feature = "s3")]
+pub struct S3Storage {
+    bucket: String,
+    region: rusoto_core::Region,
+    base_url: String,
+}
+
+#[cfg(feature = "s3")]
+impl S3Storage {
+    pub fn new(bucket: String, region: rusoto_core::Region, base_url: String) -> S3Storage {
+        S3Storage { bucket, region, base_url }
+    }
+
+    /// Run a request of the asynchronous S3 client on a runtime of its own, because Rocket's handlers are synchronous
+    fn block_on<F: std::future::Future>(future: F) -> Result<F::Output, StorageError> {
+        let mut runtime = tokio::runtime::Runtime::new().map_err(|e| StorageError::Backend(e.to_string()))?;
+        Ok(runtime.block_on(future))
+    }
+
+    fn client(&self) -> rusoto_s3::S3Client {
+        rusoto_s3::S3Client::new(self.region.clone())
+    }
+}
+
+#[cfg(feature = "s3")]
+impl Storage for S3Storage {
+    fn save(&self, key: &str, data: &[u8]) -> Result<String, StorageError> {
+        use rusoto_s3::{PutObjectRequest, S3};
+        if !valid_key(key) {
+            return Err(StorageError::InvalidKey);
+        }
+        let content_type = if key.ends_with(".jpg") { "image/jpeg" } else { "application/octet-stream" };
+        let request = PutObjectRequest {
+            bucket: self.bucket.clone(),
+            key: key.to_string(),
+            body: Some(data.to_vec().into()),
+            content_type: Some(content_type.to_string()),
+            ..Default::default()
+        };
+        S3Storage::block_on(self.client().put_object(request))?.map_err(|e| StorageError::Backend(e.to_string()))?;
+        Ok(format!("{}/{}", self.base_url.trim_end_matches('/'), key))
+    }
+
+    fn load(&self, key: &str) -> Result<Vec<u8>, StorageError> {
+        use rusoto_core::RusotoError;
+        use rusoto_s3::{GetObjectError, GetObjectRequest, S3};
+        use tokio::io::AsyncReadExt;
+        let request = GetObjectRequest {
+            bucket: self.bucket.clone(),
+            key: key.to_string(),
+            ..Default::default()
+        };
+        let client = self.client();
+        S3Storage::block_on(async move {
+            let output = match client.get_object(request).await {
+                Ok(output) => output,
+                Err(RusotoError::Service(GetObjectError::NoSuchKey(_))) => return Err(StorageError::NotFound),
+                Err(e) => return Err(StorageError::Backend(e.to_string()))
+            };
+            let mut data = Vec::new();
+            match output.body {
+                Some(body) => {
+                    body.into_async_read().read_to_end(&mut data).await.map_err(|e| StorageError::Backend(e.to_string()))?;
+                    Ok(data)
+                }
+                None => Err(StorageError::NotFound)
+            }
+        })?
+    }
+
+    fn delete(&self, key: &str) -> Result<(), StorageError> {
+        use rusoto_s3::{DeleteObjectRequest, S3};
+        let request = DeleteObjectRequest {
+            bucket: self.bucket.clone(),
+            key: key.to_string(),
+            ..Default::default()
+        };
+        S3Storage::block_on(self.client().delete_object(request))?.map(|_| ()).map_err(|e| StorageError::Backend(e.to_string()))
+    }
+
+    fn base_url(&self) -> &str {
+        &self.base_url
+    }
+}
+
+/// Create the configured storage backend. Panics if the configured backend is not available
+fn from_config(config: &config::Config) -> Box<dyn Storage> {
+    match config.get_str("storage.backend").unwrap_or_else(|_| "local".to_string()).as_str() {
+        "local" => Box::new(LocalStorage::new(
+            PathBuf::from(config.get_str("storage.directory").unwrap_or_else(|_| DEFAULT_DIRECTORY.to_string())),
+            config.get_str("storage.base_url").unwrap_or_else(|_| DEFAULT_BASE_URL.to_string()),
+        )),
+        #[cfg(feature = "s3")]
+        "s3" => {
+            let bucket = config.get_str("storage.s3_bucket").expect("storage.s3_bucket is required for the s3 backend");
+            let region: rusoto_core::Region = config.get_str("storage.s3_region").unwrap_or_else(|_| "us-east-1".to_string())
+                .parse()
+                .expect("storage.s3_region is not a valid region");
+            let base_url = config.get_str("storage.base_url")
+                .unwrap_or_else(|_| format!("https://{}.s3.{}.amazonaws.com", bucket, region.name()));
+            Box::new(S3Storage::new(bucket, region, base_url))
+        }
+        backend => panic!("Unknown storage.backend \"{}\". Use \"local\" or \"s3\" (requires the feature s3)", backend)
+    }
+}
+
+/// Manage the configured storage and serve the files of the local storage under its base URL
+pub fn mount(rocket: rocket::Rocket) -> rocket::Rocket {
+    let config = crate::load_config().unwrap_or_default();
+    let rocket = rocket.manage(from_config(&config));
+    if config.get_str("storage.backend").map(|backend| backend == "local").unwrap_or(true) {
+        let directory = config.get_str("storage.directory").unwrap_or_else(|_| DEFAULT_DIRECTORY.to_string());
+        let base_url = config.get_str("storage.base_url").unwrap_or_else(|_| DEFAULT_BASE_URL.to_string());
+        // StaticFiles needs an existing directory
+        if let Err(e) = fs::create_dir_all(&directory) {
+            panic!("Storage directory {} could not be created: {}", directory, e);
+        }
+        return rocket.mount(&base_url, StaticFiles::from(directory));
+    }
+    rocket
+}
@@ -0,0 +1,40 @@
+//! Tenants of a multi tenant installation. The tenant of a request is the one whose slug is the first label of the
+//! `Host` header, e.g. `acme` for `acme.example.com`. Requests to other hosts belong to no tenant, so installations
+//! without tenants work as before
+use rocket::Outcome;
+use rocket::request::{self, FromRequest, Request};
+
+use crate::DbConn;
+use crate::user::model::Tenant;
+
+/// Id of the tenant of the request. Is `None` if the host does not belong to a tenant
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CurrentTenant(pub Option<i32>);
+
+/// The slug of a host name: the first label without the port, e.g. `acme` for `acme.example.com:8000`
+pub fn slug_from_host(host: &str) -> Option<&str> {
+    host.split(':').next()
+        .and_then(|name| name.split('.').next())
+        .filter(|slug| !slug.is_empty())
+}
+
+/// Find the tenant of a request. The result is cached for the request
+pub fn current_tenant(request: &Request) -> CurrentTenant {
+    *request.local_cache(|| {
+        let slug = match request.headers().get_one("Host").and_then(slug_from_host) {
+            Some(slug) => slug.to_lowercase(),
+            None => return CurrentTenant(None)
+        };
+        let tenant = request.guard::<DbConn>().succeeded()
+            .and_then(|db| Tenant::by_slug(&slug, &db.0))
+            .and_then(|tenant| tenant.id);
+        CurrentTenant(tenant)
+    })
+}
+
+impl<'a, 'r> FromRequest<'a, 'r> for CurrentTenant {
+    type Error = ();
+    fn from_request(request: &'a Request<'r>) -> request::Outcome<CurrentTenant, ()> {
+        Outcome::Success(current_tenant(request))
+    }
+}
@@ -0,0 +1,91 @@
+//! Detection of slow requests. Rocket handles every request on a worker thread that can not be stopped from
+//! outside, so requests that exceed `http.request_timeout_ms` are only logged while they keep running
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use dashmap::DashMap;
+use rocket::{Data, Request, Response};
+use rocket::fairing::{Fairing, Info, Kind};
+
+use crate::request_id::RequestId;
+
+/// Time after which a request is reported if `http.request_timeout_ms` is not configured
+const DEFAULT_REQUEST_TIMEOUT_MS: u64 = 10_000;
+
+/// Attach the fairing. It has to be attached after the request id fairing, so the request id is known
+pub fn mount(rocket: rocket::Rocket) -> rocket::Rocket {
+    rocket.attach(TimeoutFairing::from_config())
+}
+
+/// A request that is currently handled
+struct RunningRequest {
+    started_at: Instant,
+    path: String,
+    /// Whether the request was already reported as slow
+    reported: bool,
+}
+
+/// Fairing that tracks the start times of the running requests. A background thread reports the requests
+/// that run longer than the timeout
+pub struct TimeoutFairing {
+    timeout: Duration,
+    /// Running requests by their request id
+    requests: Arc<DashMap<String, RunningRequest>>,
+}
+
+impl TimeoutFairing {
+    /// Read `http.request_timeout_ms` from the configuration and start the thread that checks the running
+    /// requests. Panics on invalid values
+    pub fn from_config() -> TimeoutFairing {
+        let timeout_ms = match crate::load_config().ok().and_then(|c| c.get_int("http.request_timeout_ms").ok()) {
+            Some(ms) if ms > 0 => ms as u64,
+            Some(ms) => panic!("http.request_timeout_ms has to be positive but is {}", ms),
+            None => DEFAULT_REQUEST_TIMEOUT_MS
+        };
+        let timeout = Duration::from_millis(timeout_ms);
+        let requests = Arc::new(DashMap::new());
+        let watched = Arc::clone(&requests);
+        // the requests are checked ten times per timeout, but at least every second
+        let interval = (timeout / 10).max(Duration::from_millis(10)).min(Duration::from_secs(1));
+        thread::spawn(move || loop {
+            thread::sleep(interval);
+            report_slow_requests(&watched, timeout);
+        });
+        TimeoutFairing { timeout, requests }
+    }
+}
+
+/// Log every running request that exceeds the timeout once
+fn report_slow_requests(requests: &DashMap<String, RunningRequest>, timeout: Duration) {
+    for mut request in requests.iter_mut() {
+        if !request.reported && request.started_at.elapsed() >= timeout {
+            request.reported = true;
+            warn!("[{}] Request to {} is running for more than {} ms", request.key(), request.path, timeout.as_millis());
+        }
+    }
+}
+
+impl Fairing for TimeoutFairing {
+    fn info(&self) -> Info {
+        Info {
+            name: "Request timeout",
+            kind: Kind::Request | Kind::Response,
+        }
+    }
+
+    fn on_request(&self, request: &mut Request, _data: &Data) {
+        let id = request.local_cache(|| RequestId(uuid::Uuid::new_v4().to_string())).0.clone();
+        self.requests.insert(id, RunningRequest { started_at: Instant::now(), path: request.uri().path().to_string(), reported: false });
+    }
+
+    fn on_response(&self, request: &Request, _response: &mut Response) {
+        let id = request.local_cache(|| RequestId(uuid::Uuid::new_v4().to_string())).0.clone();
+        if let Some((_, running)) = self.requests.remove(&id) {
+            // the duration of a reported request shows how long the worker thread was blocked
+            if running.reported {
+                warn!("[{}] Slow request to {} finished after {} ms (timeout {} ms)", id, running.path, running.started_at.elapsed().as_millis(), self.timeout.as_millis());
+            }
+        }
+    }
+}
@@ -0,0 +1,43 @@
+//! Translations of texts that are not part of a template, like the subjects of mails. The texts are stored in
+//! `translations/<locale>/<name>.toml`
+use config::{Config, File};
+
+/// Locale of new users and fallback for missing translations
+pub const DEFAULT_LOCALE: &str = "en";
+
+/// Name of the application in mail subjects if `app_name` is not configured
+pub const DEFAULT_APP_NAME: &str = "web_application";
+
+/// Locales that users can choose, configured with `locale.supported`. Only the default locale if not configured
+pub fn supported_locales() -> Vec<String> {
+    crate::load_config().ok()
+        .and_then(|c| c.get_array("locale.supported").ok())
+        .map(|locales| locales.into_iter().filter_map(|locale| locale.into_str().ok()).collect())
+        .unwrap_or_else(|| vec![DEFAULT_LOCALE.to_string()])
+}
+
+/// Look up a text in a translation file of a locale
+fn lookup(locale: &str, name: &str, key: &str) -> Option<String> {
+    let mut translations = Config::default();
+    translations.merge(File::with_name(&format!("translations/{}/{}", locale, name))).ok()?;
+    translations.get_str(key).ok()
+}
+
+/// Subject of a mail. A subject for the template in `[email_subjects]` of the configuration is used for all locales.
+/// Otherwise the subject is looked up by its key in `mail.toml`, e.g. `user.registration`, and falls back to the
+/// default locale and then to the key itself, so a missing translation never prevents a mail. `{app_name}` is
+/// replaced by the configured `app_name`
+pub fn mail_subject(locale: &str, template: &str, key: &str) -> String {
+    let config = crate::load_config().ok();
+    let subject = config.as_ref()
+        .and_then(|c| c.get_str(&format!("email_subjects.{}", template)).ok())
+        .filter(|subject| !subject.is_empty())
+        .or_else(|| lookup(locale, "mail", key))
+        .or_else(|| lookup(DEFAULT_LOCALE, "mail", key))
+        .unwrap_or_else(|| {
+            warn!("[{}] No translation of the mail subject {} for locale {}", crate::request_id::current(), key, locale);
+            key.to_string()
+        });
+    let app_name = config.and_then(|c| c.get_str("app_name").ok()).unwrap_or_else(|| DEFAULT_APP_NAME.to_string());
+    subject.replace("{app_name}", &app_name)
+}
@@ -1,22 +1,194 @@
 //! Auth module
 use rocket::Outcome;
 use rocket::request::{self, Request, FromRequest};
+use rocket::http::{Cookie, SameSite};
 
 pub extern crate crypto;
 pub extern crate jwt;
 pub extern crate rustc_serialize;
 
 use sha2::Sha256;
-use crate::user::model::User;
+use crate::user::model::{User, RevokedToken, Session, ApiKey};
 use crate::DbConn;
+use crate::Connection;
+use crate::tenant;
 use hmac::{Hmac, NewMac};
 use std::collections::BTreeMap;
-use self::jwt::{VerifyWithKey, Error};
+use std::time::SystemTime;
+use config::Config;
+use self::jwt::{SignWithKey, VerifyWithKey, Error};
+use uuid::Uuid;
 
-/// Read the secret key from configuration file and verify against delivered token
-pub fn read_token(token: &str) -> Result<String, String> {
-    let mut settings = config::Config::default();
-    let merged = match settings.merge(config::File::with_name("Config")) {
+/// Default lifetime of a JWT in seconds if `token_lifetime_seconds` is not configured
+const DEFAULT_TOKEN_LIFETIME: u64 = 86400;
+
+/// Calculate the expiration timestamp (Unix seconds) for a token that is issued now
+pub fn token_expiration(config: &Config) -> u64 {
+    let lifetime = match config.get_int("token_lifetime_seconds") {
+        Ok(x) if x > 0 => x as u64,
+        _ => DEFAULT_TOKEN_LIFETIME
+    };
+    SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).unwrap().as_secs() + lifetime
+}
+
+/// Sign a new token for a user and start a new session for it
+///
+/// # Arguments
+///
+/// * `user_id` - The id of the user the token is issued for
+/// * `ip` - IP address of the client
+/// * `user_agent` - User agent of the client
+/// * `config` - Application configuration
+/// * `connection` - Database connection
+pub fn issue_token(user_id: i32, ip: Option<String>, user_agent: Option<String>, config: &Config, connection: &Connection) -> Result<String, String> {
+    sign_session_token(user_id, token_expiration(config), None, ip, user_agent, config, connection).map(|(token, _)| token)
+}
+
+/// Lifetime in seconds of a token that an admin uses to impersonate a user
+pub const IMPERSONATION_TOKEN_LIFETIME: u64 = 900;
+
+/// Sign a short lived token for an admin who impersonates a user. The token carries the id of the admin in the
+/// `impersonator` claim. Returns the token, its id and its expiration timestamp
+pub fn issue_impersonation_token(user_id: i32, admin_id: i32, ip: Option<String>, user_agent: Option<String>, config: &Config, connection: &Connection) -> Result<(String, String, u64), String> {
+    let expiration = SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).unwrap().as_secs() + IMPERSONATION_TOKEN_LIFETIME;
+    sign_session_token(user_id, expiration, Some(admin_id), ip, user_agent, config, connection).map(|(token, jti)| (token, jti, expiration))
+}
+
+/// Sign a token that expires at the given timestamp and start a new session for it. Returns the token and its id
+fn sign_session_token(user_id: i32, expiration: u64, impersonator: Option<i32>, ip: Option<String>, user_agent: Option<String>, config: &Config, connection: &Connection) -> Result<(String, String), String> {
+    // find the secret key for password encryption in the configuration file
+    let secretkey = match config.get_str("secretkey") {
+        Ok(x) => { x }
+        Err(_) => { return Err("Secret key for JWT missing".to_string()); }
+    };
+    // create a new varkey from the secretkey for token
+    let key: Hmac<Sha256> = Hmac::new_varkey(secretkey.as_ref()).unwrap();
+    // a unique token id that identifies the session
+    let jti = Uuid::new_v4().to_string();
+    // create the claims object for the JWT
+    let mut claims = BTreeMap::new();
+    // insert the userid into the claims as "sub" as specified in the JWT standard
+    claims.insert("sub", user_id.to_string());
+    // insert the expiration date into the claims as "exp" as specified in the JWT standard
+    claims.insert("exp", expiration.to_string());
+    // insert a unique token id into the claims as "jti" as specified in the JWT standard
+    claims.insert("jti", jti.clone());
+    // tokens of an impersonation carry the id of the admin
+    if let Some(admin_id) = impersonator {
+        claims.insert("impersonator", admin_id.to_string());
+    }
+    // sign the token with the varkey
+    let token = match claims.sign_with_key(&key) {
+        Ok(t) => t,
+        Err(_) => return Err("Token could not be created".to_string())
+    };
+    // record the session of the token
+    if !Session::create(user_id, &jti, user_agent, ip, connection) {
+        return Err("Session could not be created".to_string());
+    }
+    Ok((token, jti))
+}
+
+/// Lifetime in seconds of the interim token that is issued after a correct password when a second factor is required
+const INTERIM_TOKEN_LIFETIME: u64 = 300;
+
+/// Sign a short lived interim token for a user who still has to provide a second factor.
+/// The interim token can only be exchanged at `POST /user/2fa/verify` and is not accepted as an access token
+pub fn issue_interim_token(user_id: i32, config: &Config) -> Result<String, String> {
+    let secretkey = match config.get_str("secretkey") {
+        Ok(x) => { x }
+        Err(_) => { return Err("Secret key for JWT missing".to_string()); }
+    };
+    let key: Hmac<Sha256> = Hmac::new_varkey(secretkey.as_ref()).unwrap();
+    let mut claims = BTreeMap::new();
+    claims.insert("sub", user_id.to_string());
+    claims.insert("exp", (SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).unwrap().as_secs() + INTERIM_TOKEN_LIFETIME).to_string());
+    // marks the token as interim token
+    claims.insert("purpose", "2fa".to_string());
+    claims.sign_with_key(&key).map_err(|_| "Token could not be created".to_string())
+}
+
+/// Verify an interim token and return the user id of the token
+pub fn read_interim_token(token: &str) -> Result<i32, String> {
+    let t = read_claims(token)?;
+    if t.get("purpose").map(String::as_str) != Some("2fa") {
+        return Err("Token not valid".to_string());
+    }
+    let expiration = match t.get("exp").and_then(|e| e.parse::<u64>().ok()) {
+        Some(e) => e,
+        None => return Err("Token not valid".to_string())
+    };
+    if expiration < SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).unwrap().as_secs() {
+        return Err("Token expired".to_string());
+    }
+    t.get("sub").and_then(|s| s.parse::<i32>().ok()).ok_or_else(|| "Token not valid".to_string())
+}
+
+/// Lifetime in seconds of the state of an OAuth2 login
+const OAUTH_STATE_LIFETIME: u64 = 600;
+
+/// Sign the state and the PKCE verifier of an OAuth2 login so they can be stored in a cookie until the callback
+pub fn issue_oauth_state(state: &str, pkce_verifier: &str, config: &Config) -> Result<String, String> {
+    let secretkey = match config.get_str("secretkey") {
+        Ok(x) => { x }
+        Err(_) => { return Err("Secret key for JWT missing".to_string()); }
+    };
+    let key: Hmac<Sha256> = Hmac::new_varkey(secretkey.as_ref()).unwrap();
+    let mut claims = BTreeMap::new();
+    claims.insert("state", state.to_string());
+    claims.insert("pkce_verifier", pkce_verifier.to_string());
+    claims.insert("exp", (SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).unwrap().as_secs() + OAUTH_STATE_LIFETIME).to_string());
+    // marks the token as OAuth2 state. It is not accepted as an access token
+    claims.insert("purpose", "oauth".to_string());
+    claims.sign_with_key(&key).map_err(|_| "Token could not be created".to_string())
+}
+
+/// Verify the signed state of an OAuth2 login and return the state and the PKCE verifier
+pub fn read_oauth_state(token: &str) -> Result<(String, String), String> {
+    let t = read_claims(token)?;
+    if t.get("purpose").map(String::as_str) != Some("oauth") {
+        return Err("Token not valid".to_string());
+    }
+    let expiration = match t.get("exp").and_then(|e| e.parse::<u64>().ok()) {
+        Some(e) => e,
+        None => return Err("Token not valid".to_string())
+    };
+    if expiration < SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).unwrap().as_secs() {
+        return Err("Token expired".to_string());
+    }
+    match (t.get("state"), t.get("pkce_verifier")) {
+        (Some(state), Some(pkce_verifier)) => Ok((state.clone(), pkce_verifier.clone())),
+        _ => Err("Token not valid".to_string())
+    }
+}
+
+/// Build the cookie for the state of an OAuth2 login. The signed state expires on its own.
+/// Unlike the session cookie it has to be sent with the redirect back from the provider, so it can not be `SameSite=Strict`
+pub fn oauth_state_cookie(value: String, config: &Config) -> Cookie<'static> {
+    let secure = config.get_bool("cookie.secure").unwrap_or(false);
+    Cookie::build("oauth_state", value)
+        .path("/user/oauth")
+        .secure(secure)
+        .http_only(true)
+        .same_site(SameSite::Lax)
+        .finish()
+}
+
+/// Build a session cookie. The cookie is never readable by JavaScript and is not sent with cross site requests.
+/// It is only sent over HTTPS if `[cookie] secure = true` is configured
+pub fn session_cookie(name: &'static str, value: String, config: &Config) -> Cookie<'static> {
+    let secure = config.get_bool("cookie.secure").unwrap_or(false);
+    Cookie::build(name, value)
+        .path("/")
+        .secure(secure)
+        .http_only(true)
+        .same_site(SameSite::Strict)
+        .finish()
+}
+
+/// Read the secret key from configuration file and return the claims of the delivered token if the signature is valid
+pub fn read_claims(token: &str) -> Result<BTreeMap<String, String>, String> {
+    let merged = match crate::load_config() {
         Ok(config) => { config }
         Err(_) => { return Err("Configuration file not found".to_string()); }
     };
@@ -28,15 +200,73 @@ pub fn read_token(token: &str) -> Result<String, String> {
     let newkey: Hmac<Sha256> = Hmac::new_varkey(secretkey.as_ref()).unwrap();
     let claims: Result<BTreeMap<String, String>, Error> = VerifyWithKey::verify_with_key(token, &newkey);
     match claims {
-        Ok(t) => {
-            if t.contains_key("sub") {
-                Ok(t["sub"].clone())
-            } else {
-                Err("Token not valid".to_string())
-            }
+        Ok(t) => Ok(t),
+        Err(_) => Err("Token not valid".to_string())
+    }
+}
+
+/// Verify the delivered token and return the user id of the token.
+/// The session of a valid token is marked as seen
+pub fn read_token(token: &str, connection: &Connection) -> Result<String, String> {
+    let t = read_claims(token)?;
+    // interim tokens of a login with a second factor are no access tokens
+    if t.contains_key("purpose") {
+        return Err("Token not valid".to_string());
+    }
+    // tokens without an expiration date are not accepted
+    let expiration = match t.get("exp").and_then(|e| e.parse::<u64>().ok()) {
+        Some(e) => e,
+        None => return Err("Token not valid".to_string())
+    };
+    if expiration < SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).unwrap().as_secs() {
+        return Err("Token expired".to_string());
+    }
+    // tokens without an id can not be revoked and are not accepted
+    let jti = match t.get("jti") {
+        Some(j) => j,
+        None => return Err("Token not valid".to_string())
+    };
+    if RevokedToken::is_revoked(jti, connection) || Session::is_revoked(jti, connection) {
+        return Err("Token revoked".to_string());
+    }
+    if t.contains_key("sub") {
+        // remember when the session was used for the last time
+        Session::touch(jti, connection);
+        Ok(t["sub"].clone())
+    } else {
+        Err("Token not valid".to_string())
+    }
+}
+
+/// Find the token of a request. A Bearer token in the Authorization header takes precedence over the cookie
+fn token_from_request(request: &Request) -> Option<String> {
+    let mut token: Option<String> = None;
+    match request.cookies().get("token") {
+        Some(c) => {
+            token = Some(c.value().to_string());
         }
-        Err(_) => {
-            Err("Token not valid".to_string())
+        None => ()
+    };
+    let keys: Vec<_> = request.headers().get("Authorization").collect();
+    if keys.len() == 1 {
+        let bearer: Vec<&str> = keys[0].split_whitespace().collect();
+        if bearer.len() == 2 {
+            token = Some(bearer.last().unwrap().to_string());
+        }
+    }
+    token
+}
+
+/// The raw token that was delivered with the request
+pub struct AuthToken(pub String);
+
+impl<'a, 'r> FromRequest<'a, 'r> for AuthToken {
+    type Error = ();
+
+    fn from_request(request: &'a Request<'r>) -> request::Outcome<AuthToken, ()> {
+        match token_from_request(request) {
+            Some(t) => Outcome::Success(AuthToken(t)),
+            None => Outcome::Forward(())
         }
     }
 }
@@ -45,37 +275,60 @@ impl<'a, 'r> FromRequest<'a, 'r> for &'a User {
     type Error = ();
 
     fn from_request(request: &'a Request<'r>) -> request::Outcome<&'a User, ()> {
-        let mut token: Option<String> = None;
-        match request.cookies().get("token") {
-            Some(c) => {
-                token = Some(c.value().to_string());
-            }
-            None => ()
-        };
-        let keys: Vec<_> = request.headers().get("Authorization").collect();
-        if keys.len() == 1 {
-            let bearer: Vec<&str> = keys[0].split_whitespace().collect();
-            if bearer.len() == 2 {
-                token = Some(bearer.last().unwrap().to_string());
+        let token = token_from_request(request);
+        // machine to machine clients can authenticate with an api key instead of a token
+        let api_key = request.headers().get_one("X-Api-Key").map(String::from);
+        if token.is_none() && api_key.is_none() {
+            return Outcome::Forward(());
+        }
+        // a token of one tenant is not accepted on the host of another tenant
+        let tenant_id = tenant::current_tenant(request).0;
+        let user_result = request.local_cache(|| {
+            let db = request.guard::<DbConn>().succeeded().unwrap();
+            match (&token, &api_key) {
+                // a token takes precedence over an api key
+                (Some(t), _) => read_token(t, &db.0).ok()
+                    .and_then(|claim| claim.parse::<i32>().ok())
+                    .and_then(|user_id| User::by_id(user_id, tenant_id, &db.0)),
+                (None, Some(k)) => ApiKey::authenticate(k, &db.0).and_then(|user_id| User::by_id(user_id, tenant_id, &db.0)),
+                (None, None) => None
             }
+        });
+        match user_result {
+            // suspended users are treated as not logged in, also with api keys
+            Some(u) if !u.is_active => { Outcome::Forward(()) }
+            Some(u) => { Outcome::Success(u) }
+            _ => { Outcome::Forward(()) }
         }
+    }
+}
 
-        match token {
-            Some(t) => {
-                let userid = match read_token(&t) {
-                    Ok(claim) => claim,
-                    Err(_) => return Outcome::Forward(())
-                };
-                let user_result = request.local_cache(|| {
-                    let db = request.guard::<DbConn>().succeeded().unwrap();
-                    User::read(userid.parse::<i32>().unwrap(), &db.0)
-                });
-                match user_result {
-                    Ok(u) => { Outcome::Success(u) }
-                    Err(_) => { Outcome::Forward(()) }
-                }
-            }
-            None => Outcome::Forward(())
+/// The id of the admin if the request uses an impersonation token. Is `None` for regular tokens.
+/// Destructive routes use it to refuse impersonated requests
+pub struct Impersonator(pub Option<i32>);
+
+impl<'a, 'r> FromRequest<'a, 'r> for Impersonator {
+    type Error = ();
+
+    fn from_request(request: &'a Request<'r>) -> request::Outcome<Impersonator, ()> {
+        let impersonator = token_from_request(request)
+            .and_then(|t| read_claims(&t).ok())
+            .and_then(|claims| claims.get("impersonator").and_then(|id| id.parse::<i32>().ok()));
+        Outcome::Success(Impersonator(impersonator))
+    }
+}
+
+/// Request guard for routes that may only be accessed by administrators.
+/// Forwards if the request has no logged in user or the user is not an admin.
+pub struct AdminUser<'a>(pub &'a User);
+
+impl<'a, 'r> FromRequest<'a, 'r> for AdminUser<'a> {
+    type Error = ();
+
+    fn from_request(request: &'a Request<'r>) -> request::Outcome<AdminUser<'a>, ()> {
+        match request.guard::<&User>() {
+            Outcome::Success(user) if user.role == "admin" => Outcome::Success(AdminUser(user)),
+            _ => Outcome::Forward(())
         }
     }
 }
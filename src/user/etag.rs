@@ -0,0 +1,83 @@
+//! ETags for conditional GET requests of the user profile
+use rocket::{Outcome, Request, Response};
+use rocket::http::Status;
+use rocket::request::{self, FromRequest};
+use rocket::response::{self, Responder};
+use sha2::{Digest, Sha256};
+
+use crate::user::model::User;
+
+/// Compute a strong ETag for the serialized profile of a user from the id, the edit date and a hash of the image.
+/// The edit date only has a resolution of seconds, so the other fields of the profile are part of the ETag as well
+pub fn etag(user: &User) -> String {
+    let image_hash = match &user.image {
+        Some(image) => format!("{:x}", Sha256::digest(image)),
+        None => String::new()
+    };
+    let mut hasher = Sha256::new();
+    hasher.update(format!("{:?}|{}|{}|{:?}", user.id, user.edit_date, image_hash, user.image_thumb_url));
+    hasher.update(format!("|{:?}|{:?}", user.image_medium_url, user.image_full_url));
+    hasher.update(format!("|{}|{:?}|{:?}", user.firstname, user.lastname, user.phone));
+    hasher.update(format!("|{}|{:?}|{}|{}", user.email, user.pending_email, user.registration_code.is_some(), user.totp_enabled));
+    hasher.update(format!("|{:?}|{:?}|{:?}|{:?}", user.last_login_at, user.last_login_ip, user.username, user.timezone));
+    hasher.update(format!("|{}|{:?}|{:?}|{:?}", user.locale, user.bio, user.website, user.avatar_url));
+    format!("\"{:x}\"", hasher.finalize())
+}
+
+/// The `If-None-Match` header of a request. Is empty if the header is missing
+pub struct IfNoneMatch(pub Option<String>);
+
+impl IfNoneMatch {
+    /// Check whether the client already has the representation with the given ETag
+    pub fn matches(&self, etag: &str) -> bool {
+        match &self.0 {
+            Some(header) => header.split(',')
+                .map(|tag| tag.trim())
+                // weak comparison as specified for If-None-Match
+                .any(|tag| tag == "*" || tag.trim_start_matches("W/") == etag),
+            None => false
+        }
+    }
+}
+
+impl<'a, 'r> FromRequest<'a, 'r> for IfNoneMatch {
+    type Error = ();
+
+    fn from_request(request: &'a Request<'r>) -> request::Outcome<IfNoneMatch, ()> {
+        Outcome::Success(IfNoneMatch(request.headers().get_one("If-None-Match").map(String::from)))
+    }
+}
+
+/// Responder that sets the `ETag` header. Responds with `304 Not Modified` and no body if the client
+/// already has the current representation
+pub enum ETagged<R> {
+    /// The client has the current representation
+    NotModified(String),
+    /// The representation has changed or the client has none
+    Modified(R, String),
+}
+
+impl<R> ETagged<R> {
+    /// Respond with the body unless the `If-None-Match` header of the request matches the ETag
+    pub fn new(body: R, etag: String, if_none_match: &IfNoneMatch) -> ETagged<R> {
+        if if_none_match.matches(&etag) {
+            ETagged::NotModified(etag)
+        } else {
+            ETagged::Modified(body, etag)
+        }
+    }
+}
+
+impl<'r, R: Responder<'r>> Responder<'r> for ETagged<R> {
+    fn respond_to(self, request: &Request) -> response::Result<'r> {
+        match self {
+            ETagged::NotModified(etag) => Response::build()
+                .status(Status::NotModified)
+                .raw_header("ETag", etag)
+                .ok(),
+            ETagged::Modified(body, etag) => Response::build_from(body.respond_to(request)?)
+                .raw_header("ETag", etag)
+                .ok()
+        }
+    }
+}
@@ -1,13 +1,19 @@
 use diesel;
 use diesel::prelude::*;
-use diesel::mysql::MysqlConnection;
-use crate::user::schema::users;
+use diesel::Connection as _;
+use crate::{Connection, SecuritySettings};
+use crate::user::schema::{users, tenants, refresh_tokens, login_attempts, revoked_tokens, sessions, email_log, backup_codes, phone_otps, password_history, invitations, impersonation_log, api_keys, webhooks, webhook_deliveries, audit_log, oauth_accounts, notifications, email_preferences, feedback};
 use bcrypt::{verify};
 use serde::ser::{Serialize, Serializer, SerializeStruct};
+use schemars::JsonSchema;
 use crate::user::NewUser;
+use crate::user::oauth::{GoogleUser, OAuthTokens};
+use crate::user::totp;
 use rand::Rng;
 use rand::distributions::Alphanumeric;
 use std::time::SystemTime;
+use config::Config;
+use crate::translations;
 
 #[table_name = "users"]
 #[changeset_options(treat_none_as_null = "true")]
@@ -20,50 +26,287 @@ pub struct User {
     pub registration_code: Option<String>,
     pub reset_code: Option<String>,
     pub image: Option<Vec<u8>>,
-    pub create_date: u64,
-    pub edit_date: u64
+    pub create_date: i64,
+    pub edit_date: i64,
+    pub role: String,
+    pub deleted_at: Option<i64>,
+    pub reset_code_expires_at: Option<i64>,
+    pub registration_code_expires_at: Option<i64>,
+    pub lastname: Option<String>,
+    pub phone: Option<String>,
+    /// The phone number was confirmed with an OTP that was sent to it by SMS
+    pub phone_verified: bool,
+    pub last_login_at: Option<i64>,
+    pub last_login_ip: Option<String>,
+    pub pending_email: Option<String>,
+    pub email_confirmation_code: Option<String>,
+    pub totp_secret: Option<String>,
+    pub totp_enabled: bool,
+    pub google_sub: Option<String>,
+    pub image_thumb_url: Option<String>,
+    pub image_medium_url: Option<String>,
+    pub image_full_url: Option<String>,
+    pub is_active: bool,
+    pub username: Option<String>,
+    /// IANA name of the timezone of the user, e.g. `Europe/Berlin`
+    pub timezone: Option<String>,
+    /// Language of the mails to the user
+    pub locale: String,
+    /// Short description of the user with at most 500 characters
+    pub bio: Option<String>,
+    /// http or https URL of the website of the user
+    pub website: Option<String>,
+    /// https URL of an avatar from an external service like Gravatar. Is preferred over an uploaded image
+    pub avatar_url: Option<String>,
+    /// Tenant the user belongs to. Users of single tenant installations have no tenant
+    pub tenant_id: Option<i32>,
 }
 
-impl Serialize for User {
+/// View of a user for API responses. Only contains fields that are safe to show to the user and to other
+/// services. Codes, secrets and internal fields are never serialized
+pub struct UserPublic<'a>(pub &'a User);
+
+/// View of a user for admin routes. Additionally to the fields of `UserPublic` it contains the last login ip, the
+/// pending email address and codes, the role, the state of the account and the timestamps of the record
+pub struct UserInternal<'a>(pub &'a User);
+
+/// View of a user for the public profile that can be read without authentication. Only contains the name and
+/// the profile image
+pub struct UserProfile<'a>(pub &'a User);
+
+/// Serialize the fields of `UserPublic`
+fn serialize_public_fields<S: SerializeStruct>(user: &User, state: &mut S) -> Result<(), S::Error> {
+    // Prepare user for serialization
+    // return is_activated == true if regcode is empty
+    let is_confirmed = match &user.registration_code {
+        Some(_) => false,
+        None => true
+    };
+
+    let userimage = user.image_url();
+
+    // full name so that clients do not have to concatenate it
+    let display_name = format!("{} {}", user.firstname, user.lastname.as_ref().map(String::as_str).unwrap_or("")).trim().to_string();
+
+    state.serialize_field("id", &user.id)?;
+    state.serialize_field("username", &user.username)?;
+    state.serialize_field("email", &user.email)?;
+    state.serialize_field("firstname", &user.firstname)?;
+    state.serialize_field("lastname", &user.lastname)?;
+    state.serialize_field("display_name", &display_name)?;
+    state.serialize_field("phone", &user.phone)?;
+    state.serialize_field("phone_verified", &user.phone_verified)?;
+    state.serialize_field("bio", &user.bio)?;
+    state.serialize_field("website", &user.website)?;
+    state.serialize_field("is_confirmed", &is_confirmed)?;
+    state.serialize_field("image", &userimage)?;
+    state.serialize_field("avatar_url", &user.avatar_url)?;
+    state.serialize_field("image_thumb_url", &user.image_thumb_url)?;
+    state.serialize_field("image_medium_url", &user.image_medium_url)?;
+    state.serialize_field("image_full_url", &user.image_full_url)?;
+    state.serialize_field("last_login_at", &user.last_login_at)?;
+    state.serialize_field("timezone", &user.timezone)?;
+    state.serialize_field("locale", &user.locale)
+}
+
+impl<'a> Serialize for UserPublic<'a> {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
         where
             S: Serializer,
     {
-        // Prepare user for serialization
-        // return is_activated == true if regcode is empty
-        let is_confirmed = match &self.registration_code {
-            Some(_) => false,
-            None => true
-        };
+        // 19 is the number of serialized fields
+        let mut state = serializer.serialize_struct("User", 19)?;
+        serialize_public_fields(self.0, &mut state)?;
+        state.end()
+    }
+}
 
-        let userimage = match &self.image {
-            Some(image) => {
-                Some(String::from("data:image/jpeg;base64,") + &base64::encode(&image))
-            }
-            None => None
-        };
+impl<'a> Serialize for UserProfile<'a> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+        where
+            S: Serializer,
+    {
+        let display_name = format!("{} {}", self.0.firstname, self.0.lastname.as_ref().map(String::as_str).unwrap_or("")).trim().to_string();
+        let mut state = serializer.serialize_struct("UserProfile", 11)?;
+        state.serialize_field("id", &self.0.id)?;
+        state.serialize_field("username", &self.0.username)?;
+        state.serialize_field("firstname", &self.0.firstname)?;
+        state.serialize_field("lastname", &self.0.lastname)?;
+        state.serialize_field("display_name", &display_name)?;
+        state.serialize_field("bio", &self.0.bio)?;
+        state.serialize_field("website", &self.0.website)?;
+        state.serialize_field("avatar_url", &self.0.avatar_url)?;
+        state.serialize_field("image_thumb_url", &self.0.image_thumb_url)?;
+        state.serialize_field("image_medium_url", &self.0.image_medium_url)?;
+        state.serialize_field("image_full_url", &self.0.image_full_url)?;
+        state.end()
+    }
+}
 
-        // 13 is the number of fields in the struct.
-        let mut state = serializer.serialize_struct("User", 15)?;
-        state.serialize_field("id", &self.id)?;
-        state.serialize_field("email", &self.email)?;
-        state.serialize_field("firstname", &self.firstname)?;
-        state.serialize_field("is_confirmed", &is_confirmed)?;
-        state.serialize_field("image", &userimage)?;
+impl<'a> Serialize for UserInternal<'a> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+        where
+            S: Serializer,
+    {
+        // 19 public and 13 internal fields
+        let mut state = serializer.serialize_struct("User", 32)?;
+        serialize_public_fields(self.0, &mut state)?;
+        state.serialize_field("last_login_ip", &self.0.last_login_ip)?;
+        state.serialize_field("pending_email", &self.0.pending_email)?;
+        state.serialize_field("totp_enabled", &self.0.totp_enabled)?;
+        state.serialize_field("is_active", &self.0.is_active)?;
+        state.serialize_field("registration_code", &self.0.registration_code)?;
+        state.serialize_field("registration_code_expires_at", &self.0.registration_code_expires_at)?;
+        state.serialize_field("reset_code", &self.0.reset_code)?;
+        state.serialize_field("reset_code_expires_at", &self.0.reset_code_expires_at)?;
+        state.serialize_field("role", &self.0.role)?;
+        state.serialize_field("google_linked", &self.0.google_sub.is_some())?;
+        state.serialize_field("create_date", &self.0.create_date)?;
+        state.serialize_field("edit_date", &self.0.edit_date)?;
+        state.serialize_field("deleted_at", &self.0.deleted_at)?;
         state.end()
     }
 }
 
-impl From<NewUser> for User {
-    fn from(newuser: NewUser) -> Self {
+/// Aggregate counts of the users for the admin dashboard
+#[derive(Serialize, Debug, PartialEq, Clone, Default)]
+pub struct UserStats {
+    pub total_users: i64,
+    /// Users that are activated and not suspended
+    pub active_users: i64,
+    /// Users that have not clicked the activation link yet
+    pub unactivated_users: i64,
+    pub users_created_today: i64,
+    /// Users created since Monday
+    pub users_created_this_week: i64,
+    pub failed_logins_today: i64,
+}
+
+/// Default lifetime of a registration code in seconds if `registration_code_lifetime_seconds` is not configured
+const DEFAULT_REGISTRATION_CODE_LIFETIME: i64 = 172800;
+
+/// Calculate the expiration timestamp (Unix seconds) for a registration code that is created now
+pub fn registration_code_expiration() -> i64 {
+    let lifetime = match crate::load_config() {
+        Ok(config) => match config.get_int("registration_code_lifetime_seconds") {
+            Ok(x) if x > 0 => x,
+            _ => DEFAULT_REGISTRATION_CODE_LIFETIME
+        },
+        Err(_) => DEFAULT_REGISTRATION_CODE_LIFETIME
+    };
+    SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).unwrap().as_secs() as i64 + lifetime
+}
+
+/// Roles of users. Stored as lowercase string in the `role` column
+#[derive(Deserialize, JsonSchema, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    User,
+    Admin,
+}
+
+impl Role {
+    /// The value of the `role` column
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Role::User => "user",
+            Role::Admin => "admin",
+        }
+    }
+}
+
+/// Columns by which lists of users can be sorted
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum UserSort {
+    Id,
+    Email,
+    Firstname,
+    Lastname,
+    CreateDate,
+    LastLoginAt,
+}
+
+impl UserSort {
+    /// The sort column by its name in the `sort` query parameter
+    pub fn from_name(name: &str) -> Option<UserSort> {
+        match name {
+            "id" => Some(UserSort::Id),
+            "email" => Some(UserSort::Email),
+            "firstname" => Some(UserSort::Firstname),
+            "lastname" => Some(UserSort::Lastname),
+            "create_date" => Some(UserSort::CreateDate),
+            "last_login_at" => Some(UserSort::LastLoginAt),
+            _ => None
+        }
+    }
+}
+
+/// Build a LIKE pattern that matches values containing the given text. Wildcards in the text are escaped
+fn like_pattern(text: &str) -> String {
+    let escaped = text.replace('\\', "\\\\").replace('%', "\\%").replace('_', "\\_");
+    format!("%{}%", escaped)
+}
+
+impl User {
+    /// URL of the profile image. An external avatar URL is preferred over the uploaded thumbnail. Images that were
+    /// uploaded before the storage existed are still stored in the database and are returned as data URI
+    pub fn image_url(&self) -> Option<String> {
+        if let Some(avatar_url) = &self.avatar_url {
+            return Some(avatar_url.clone());
+        }
+        match (&self.image_thumb_url, &self.image) {
+            (Some(url), _) => Some(url.clone()),
+            (None, Some(image)) => Some(String::from("data:image/jpeg;base64,") + &base64::encode(image)),
+            (None, None) => None
+        }
+    }
+
+    /// URLs of all stored variants of the profile image
+    pub fn image_urls(&self) -> Vec<&String> {
+        vec![&self.image_thumb_url, &self.image_medium_url, &self.image_full_url].into_iter().flatten().collect()
+    }
+
+    /// A copy of the user with a new random registration code that expires after the configured lifetime
+    pub fn new_registration_code(&self, security: &SecuritySettings) -> User {
         // create an random alphanumeric code
-        let registration_code: String = rand::thread_rng().sample_iter(&Alphanumeric).take(8).collect();
+        let registration_code: String = rand::thread_rng().sample_iter(&Alphanumeric).take(security.registration_code_length).collect();
         User {
-            email: newuser.email,
-            password: bcrypt::hash(&newuser.password, bcrypt::DEFAULT_COST).unwrap(),
             registration_code: Some(registration_code),
-            create_date: SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).unwrap().as_secs(),
-            edit_date: SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).unwrap().as_secs(),
+            registration_code_expires_at: Some(registration_code_expiration()),
+            ..self.clone()
+        }
+    }
+
+    /// Prepare a new user from a NewUser object
+    pub fn from_new_user(newuser: NewUser, security: &SecuritySettings) -> User {
+        User {
+            // emails are stored in lowercase, so lookups do not depend on the collation of the column
+            email: newuser.email.to_lowercase(),
+            password: bcrypt::hash(&newuser.password, security.bcrypt_cost).unwrap(),
+            create_date: SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).unwrap().as_secs() as i64,
+            edit_date: SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).unwrap().as_secs() as i64,
+            role: String::from("user"),
+            is_active: true,
+            locale: translations::DEFAULT_LOCALE.to_string(),
+            ..Default::default()
+        }.new_registration_code(security)
+    }
+
+    /// Prepare a new user for a Google account. The email address is already confirmed by Google, so no registration
+    /// code is needed. No password is set, it can only be set with a password reset
+    pub fn from_google_user(google_user: &GoogleUser) -> User {
+        User {
+            email: google_user.email.to_lowercase(),
+            firstname: google_user.given_name.clone().unwrap_or_default(),
+            lastname: google_user.family_name.clone(),
+            // no password is set until the user resets it, so the login with a password is not possible
+            password: String::new(),
+            google_sub: Some(google_user.sub.clone()),
+            create_date: SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).unwrap().as_secs() as i64,
+            edit_date: SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).unwrap().as_secs() as i64,
+            role: String::from("user"),
+            is_active: true,
+            locale: translations::DEFAULT_LOCALE.to_string(),
             ..Default::default()
         }
     }
@@ -71,18 +314,47 @@ impl From<NewUser> for User {
 
 
 impl User {
-    /// Find user by registration code
-    pub fn by_registration_code(registration_code: String, connection: &MysqlConnection) -> Option<User> {
-        users::table.filter(users::registration_code.eq(registration_code)).order(users::id).first::<User>(connection).ok()
+    /// Users that are not deleted and belong to the tenant. Users without a tenant are found with `None`, unless
+    /// `all_if_none` is set. Then `None` finds the users of all tenants, like admins without a tenant see them
+    fn of_tenant(tenant_id: Option<i32>, all_if_none: bool) -> users::BoxedQuery<'static, <Connection as diesel::Connection>::Backend> {
+        let query = users::table.filter(users::deleted_at.is_null()).into_boxed();
+        match tenant_id {
+            Some(tenant_id) => query.filter(users::tenant_id.eq(tenant_id)),
+            None if all_if_none => query,
+            None => query.filter(users::tenant_id.is_null())
+        }
+    }
+
+    /// Counts of the users for the admin dashboard. Every number is counted by the database, the rows are not loaded.
+    /// `today` and `week_start` are Unix timestamps of the start of the current day and week
+    pub fn stats(tenant_id: Option<i32>, today: i64, week_start: i64, connection: &Connection) -> QueryResult<UserStats> {
+        Ok(UserStats {
+            total_users: User::of_tenant(tenant_id, true).count().get_result(connection)?,
+            active_users: User::of_tenant(tenant_id, true).filter(users::is_active.eq(true)).filter(users::registration_code.is_null()).count().get_result(connection)?,
+            unactivated_users: User::of_tenant(tenant_id, true).filter(users::registration_code.is_not_null()).count().get_result(connection)?,
+            users_created_today: User::of_tenant(tenant_id, true).filter(users::create_date.ge(today)).count().get_result(connection)?,
+            users_created_this_week: User::of_tenant(tenant_id, true).filter(users::create_date.ge(week_start)).count().get_result(connection)?,
+            failed_logins_today: LoginAttempt::count_since(today, tenant_id, connection)?,
+        })
+    }
+
+    /// Find user of a tenant by registration code
+    pub fn by_registration_code(registration_code: String, tenant_id: Option<i32>, connection: &Connection) -> Option<User> {
+        User::of_tenant(tenant_id, false).filter(users::registration_code.eq(registration_code)).order(users::id).first::<User>(connection).ok()
+    }
+    /// Find user of a tenant by reset code
+    pub fn by_reset_code(reset_code: String, tenant_id: Option<i32>, connection: &Connection) -> Option<User> {
+        User::of_tenant(tenant_id, false).filter(users::reset_code.eq(reset_code)).order(users::id).first::<User>(connection).ok()
     }
-    /// Find user by registration code
-    pub fn by_reset_code(reset_code: String, connection: &MysqlConnection) -> Option<User> {
-        users::table.filter(users::reset_code.eq(reset_code)).order(users::id).first::<User>(connection).ok()
+
+    /// Find a user of a tenant by the confirmation code of a pending email address change
+    pub fn by_email_confirmation_code(email_confirmation_code: &str, tenant_id: Option<i32>, connection: &Connection) -> Option<User> {
+        User::of_tenant(tenant_id, false).filter(users::email_confirmation_code.eq(email_confirmation_code)).filter(users::pending_email.is_not_null()).order(users::id).first::<User>(connection).ok()
     }
 
-    /// Find a user by Username and Password
-    pub fn by_username_and_password(email: &str, password: &str, connection: &MysqlConnection) -> Option<User> {
-        match users::table.filter(users::email.eq(email)).order(users::id).first::<User>(connection) {
+    /// Find a user of a tenant by Username and Password
+    pub fn by_username_and_password(email: &str, password: &str, tenant_id: Option<i32>, connection: &Connection) -> Option<User> {
+        match User::of_tenant(tenant_id, false).filter(users::email.eq(email.to_lowercase())).order(users::id).first::<User>(connection) {
             Ok(user) => {
                 match verify(password, &user.password.clone()) {
                     Ok(x) if x == true => Some(user),
@@ -93,23 +365,70 @@ impl User {
         }
     }
 
-    /// Find user by email
-    pub fn by_email(email: &str, connection: &MysqlConnection) -> Option<User> {
-        users::table.filter(users::email.eq(email)).order(users::id).first::<User>(connection).ok()
+    /// Find the user of a tenant that is linked to an account of an OAuth provider
+    pub fn by_oauth_account(provider: &str, provider_sub: &str, tenant_id: Option<i32>, connection: &Connection) -> Option<User> {
+        let account = OAuthAccount::by_provider_sub(provider, provider_sub, connection)?;
+        User::by_id(account.user_id, tenant_id, connection)
+    }
+
+    /// Whether an admin can see and change the user. Admins of a tenant only manage the users of their tenant
+    pub fn is_managed_by(&self, admin: &User) -> bool {
+        admin.tenant_id.is_none() || admin.tenant_id == self.tenant_id
+    }
+
+    /// Whether the user has set a password. Users that were created with an OAuth login have an empty password hash
+    pub fn has_password(&self) -> bool {
+        !self.password.is_empty()
+    }
+
+    /// Find user of a tenant by id
+    pub fn by_id(id: i32, tenant_id: Option<i32>, connection: &Connection) -> Option<User> {
+        User::of_tenant(tenant_id, false).filter(users::id.eq(id)).first::<User>(connection).ok()
+    }
+
+    /// Find a user by id that the admin can see and change. Admins of a tenant only find the users of their tenant
+    pub fn by_id_managed_by(id: i32, admin: &User, connection: &Connection) -> Option<User> {
+        User::of_tenant(admin.tenant_id, true).filter(users::id.eq(id)).first::<User>(connection).ok()
+    }
+
+    /// Check whether a user of the tenant with the email exists. Soft deleted users are counted as well, because
+    /// they keep their email address until they are removed or restored. Only counts the users instead of loading
+    /// the whole row with the image
+    pub fn exists_by_email(email: &str, tenant_id: Option<i32>, connection: &Connection) -> bool {
+        let query = users::table.select(diesel::dsl::count_star()).filter(users::email.eq(email.to_lowercase())).into_boxed();
+        let query = match tenant_id {
+            Some(tenant_id) => query.filter(users::tenant_id.eq(tenant_id)),
+            None => query.filter(users::tenant_id.is_null())
+        };
+        query
+            .first::<i64>(connection)
+            .map(|count| count > 0)
+            .unwrap_or(false)
+    }
+
+    /// Find user of a tenant by username
+    pub fn by_username(username: &str, tenant_id: Option<i32>, connection: &Connection) -> Option<User> {
+        User::of_tenant(tenant_id, false).filter(users::username.eq(username)).first::<User>(connection).ok()
+    }
+
+    /// Find user of a tenant by email
+    pub fn by_email(email: &str, tenant_id: Option<i32>, connection: &Connection) -> Option<User> {
+        User::of_tenant(tenant_id, false).filter(users::email.eq(email.to_lowercase())).order(users::id).first::<User>(connection).ok()
     }
 
-    pub fn create(user: User, connection: &MysqlConnection) -> QueryResult<User> {
+    pub fn create(user: User, connection: &Connection) -> QueryResult<User> {
         diesel::insert_into(users::table).values(&user).execute(connection)?;
         users::table.order(users::id.desc()).first(connection)
     }
 
-    pub fn read(id: i32, connection: &MysqlConnection) -> QueryResult<User> {
-        users::table.find(id).first::<User>(connection)
+    #[deprecated(note = "use User::by_id")]
+    pub fn read(id: i32, connection: &Connection) -> QueryResult<User> {
+        users::table.find(id).filter(users::deleted_at.is_null()).first::<User>(connection)
     }
 
-    /// Find a user by Username and Password
-    pub fn by_email_and_password(email: &str, password: &str, connection: &MysqlConnection) -> Option<User> {
-        match users::table.filter(users::email.eq(email)).order(users::id).first::<User>(connection) {
+    /// Find an active user of a tenant by Username and Password
+    pub fn by_email_and_password(email: &str, password: &str, tenant_id: Option<i32>, connection: &Connection) -> Option<User> {
+        match User::of_tenant(tenant_id, false).filter(users::email.eq(email.to_lowercase())).filter(users::is_active.eq(true)).order(users::id).first::<User>(connection) {
             Ok(user) => {
                 match verify(password, &user.password) {
                     Ok(x) if x == true => Some(user),
@@ -120,11 +439,1322 @@ impl User {
         }
     }
 
-    pub fn update(user: &User, connection: &MysqlConnection) -> bool {
-        diesel::update(users::table.find(user.id.unwrap())).set(user).execute(connection).is_ok()
+    /// Check whether the email and password belong to a suspended user of the tenant
+    pub fn is_suspended(email: &str, password: &str, tenant_id: Option<i32>, connection: &Connection) -> bool {
+        match User::of_tenant(tenant_id, false).filter(users::email.eq(email.to_lowercase())).filter(users::is_active.eq(false)).order(users::id).first::<User>(connection) {
+            Ok(user) => verify(password, &user.password).unwrap_or(false),
+            Err(_) => false
+        }
+    }
+
+    /// Copy of a user with the edit date set to the current time
+    fn touched(user: &User) -> User {
+        User {
+            edit_date: SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).unwrap().as_secs() as i64,
+            ..user.clone()
+        }
+    }
+
+    /// Update a user. The edit date is always set to the current time
+    pub fn update(user: &User, connection: &Connection) -> bool {
+        let user = User::touched(user);
+        diesel::update(users::table.find(user.id.unwrap())).set(&user).execute(connection).is_ok()
+    }
+
+    /// Update a user in a transaction. The changes are rolled back unless exactly one user is updated.
+    /// The edit date is always set to the current time
+    pub fn update_in_transaction(user: &User, connection: &Connection) -> QueryResult<()> {
+        let user = User::touched(user);
+        connection.transaction(|| {
+            match diesel::update(users::table.find(user.id.unwrap())).set(&user).execute(connection)? {
+                1 => Ok(()),
+                _ => Err(diesel::result::Error::RollbackTransaction)
+            }
+        })
+    }
+
+    /// Save a new user together with the link of an account of a provider. Either both are saved or none, so no
+    /// user is left behind if the account is already linked
+    pub fn create_with_oauth_account(user: User, mut account: OAuthAccount, connection: &Connection) -> QueryResult<User> {
+        connection.transaction(|| {
+            let user = User::create(user, connection)?;
+            account.user_id = user.id.unwrap();
+            OAuthAccount::create(account, connection)?;
+            Ok(user)
+        })
+    }
+
+    /// Update a user and link an account of a provider to it in one transaction. The edit date is always set to
+    /// the current time
+    pub fn update_with_oauth_account(user: &User, mut account: OAuthAccount, connection: &Connection) -> QueryResult<()> {
+        let user = User::touched(user);
+        connection.transaction(|| {
+            match diesel::update(users::table.find(user.id.unwrap())).set(&user).execute(connection)? {
+                1 => {
+                    account.user_id = user.id.unwrap();
+                    OAuthAccount::create(account, connection).map(|_| ())
+                }
+                _ => Err(diesel::result::Error::RollbackTransaction)
+            }
+        })
+    }
+
+    /// Search users with optional email, first name and tenant filters.
+    /// Returns one page of the matching users and the total number of matches
+    pub fn search(email: Option<&str>, firstname: Option<&str>, tenant_id: Option<i32>, offset: i64, limit: i64, connection: &Connection) -> QueryResult<(Vec<User>, i64)> {
+        User::list(email, firstname, tenant_id, UserSort::Id, false, offset, limit, connection)
+    }
+
+    /// List users with optional email, first name and tenant filters, sorted by a column. Without a tenant the users
+    /// of all tenants are listed. Users with the same value are sorted by id, so the pages are stable. Returns one
+    /// page of the matching users and the total number of matches
+    pub fn list(email: Option<&str>, firstname: Option<&str>, tenant_id: Option<i32>, sort: UserSort, descending: bool, offset: i64, limit: i64, connection: &Connection) -> QueryResult<(Vec<User>, i64)> {
+        let mut query = User::of_tenant(tenant_id, true);
+        let mut count_query = User::of_tenant(tenant_id, true);
+        if let Some(email) = email {
+            query = query.filter(users::email.like(like_pattern(email)).escape('\\'));
+            count_query = count_query.filter(users::email.like(like_pattern(email)).escape('\\'));
+        }
+        if let Some(firstname) = firstname {
+            query = query.filter(users::firstname.like(like_pattern(firstname)).escape('\\'));
+            count_query = count_query.filter(users::firstname.like(like_pattern(firstname)).escape('\\'));
+        }
+        query = match (sort, descending) {
+            (UserSort::Id, false) => query.order(users::id.asc()),
+            (UserSort::Id, true) => query.order(users::id.desc()),
+            (UserSort::Email, false) => query.order(users::email.asc()),
+            (UserSort::Email, true) => query.order(users::email.desc()),
+            (UserSort::Firstname, false) => query.order(users::firstname.asc()),
+            (UserSort::Firstname, true) => query.order(users::firstname.desc()),
+            (UserSort::Lastname, false) => query.order(users::lastname.asc()),
+            (UserSort::Lastname, true) => query.order(users::lastname.desc()),
+            (UserSort::CreateDate, false) => query.order(users::create_date.asc()),
+            (UserSort::CreateDate, true) => query.order(users::create_date.desc()),
+            (UserSort::LastLoginAt, false) => query.order(users::last_login_at.asc()),
+            (UserSort::LastLoginAt, true) => query.order(users::last_login_at.desc()),
+        };
+        let total = count_query.count().get_result::<i64>(connection)?;
+        let users = query.then_order_by(users::id).limit(limit).offset(offset).load::<User>(connection)?;
+        Ok((users, total))
+    }
+
+    /// List users with optional email, first name and tenant filters whose id is greater than `after_id`, sorted by
+    /// id. Uses the primary key instead of an offset, so late pages of large tables are as fast as the first one
+    pub fn list_after(email: Option<&str>, firstname: Option<&str>, tenant_id: Option<i32>, after_id: i32, limit: i64, connection: &Connection) -> QueryResult<Vec<User>> {
+        let mut query = User::of_tenant(tenant_id, true).filter(users::id.gt(after_id));
+        if let Some(email) = email {
+            query = query.filter(users::email.like(like_pattern(email)).escape('\\'));
+        }
+        if let Some(firstname) = firstname {
+            query = query.filter(users::firstname.like(like_pattern(firstname)).escape('\\'));
+        }
+        query.order(users::id.asc()).limit(limit).load::<User>(connection)
+    }
+
+    /// Check if the reset code of the user is expired. Reset codes without expiration date are treated as expired
+    pub fn reset_code_expired(&self) -> bool {
+        match self.reset_code_expires_at {
+            Some(expires_at) => expires_at <= SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).unwrap().as_secs() as i64,
+            None => true
+        }
+    }
+
+    /// Check if the registration code of the user is expired. Registration codes without expiration date are treated as expired
+    pub fn registration_code_expired(&self) -> bool {
+        match self.registration_code_expires_at {
+            Some(expires_at) => expires_at <= SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).unwrap().as_secs() as i64,
+            None => true
+        }
     }
 
-    pub fn delete(id: i32, connection: &MysqlConnection) -> bool {
-        diesel::delete(users::table.find(id)).execute(connection).is_ok()
+    /// Change the role of a user. The edit date is set to the current time. Returns false if no user was changed
+    pub fn set_role(id: i32, role: Role, connection: &Connection) -> bool {
+        let now = SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).unwrap().as_secs() as i64;
+        diesel::update(users::table.find(id).filter(users::deleted_at.is_null()))
+            .set((users::role.eq(role.as_str()), users::edit_date.eq(now)))
+            .execute(connection)
+            .map(|count| count == 1)
+            .unwrap_or(false)
     }
-}
\ No newline at end of file
+
+    /// Mark a user as deleted. The user is kept in the database but is not returned by any lookup
+    pub fn soft_delete(user: &User, connection: &Connection) -> bool {
+        let deleted = User {
+            deleted_at: Some(SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).unwrap().as_secs() as i64),
+            ..user.clone()
+        };
+        User::update(&deleted, connection)
+    }
+
+    /// Restore a soft deleted user
+    pub fn restore(id: i32, connection: &Connection) -> bool {
+        diesel::update(users::table.find(id)).set(users::deleted_at.eq(None::<i64>)).execute(connection).is_ok()
+    }
+
+    /// Find a user by id, also if the user is soft deleted
+    pub fn by_id_with_deleted(id: i32, connection: &Connection) -> Option<User> {
+        users::table.find(id).first::<User>(connection).ok()
+    }
+
+    /// Permanently remove a user from the database. The rows of other tables that belong to the user are removed
+    /// in the same transaction, so no rows point to a missing user
+    pub fn hard_delete(user: &User, connection: &Connection) -> bool {
+        let id = user.id.unwrap();
+        connection.transaction::<_, diesel::result::Error, _>(|| {
+            diesel::delete(sessions::table.filter(sessions::user_id.eq(id))).execute(connection)?;
+            diesel::delete(refresh_tokens::table.filter(refresh_tokens::user_id.eq(id))).execute(connection)?;
+            diesel::delete(audit_log::table.filter(audit_log::user_id.eq(id))).execute(connection)?;
+            diesel::delete(email_log::table.filter(email_log::user_id.eq(id))).execute(connection)?;
+            diesel::delete(api_keys::table.filter(api_keys::user_id.eq(id))).execute(connection)?;
+            diesel::delete(backup_codes::table.filter(backup_codes::user_id.eq(id))).execute(connection)?;
+            diesel::delete(phone_otps::table.filter(phone_otps::user_id.eq(id))).execute(connection)?;
+            diesel::delete(password_history::table.filter(password_history::user_id.eq(id))).execute(connection)?;
+            diesel::delete(oauth_accounts::table.filter(oauth_accounts::user_id.eq(id))).execute(connection)?;
+            diesel::delete(notifications::table.filter(notifications::user_id.eq(id))).execute(connection)?;
+            diesel::delete(email_preferences::table.filter(email_preferences::user_id.eq(id))).execute(connection)?;
+            diesel::delete(feedback::table.filter(feedback::user_id.eq(id))).execute(connection)?;
+            // impersonations that the user performed as admin stay recorded, they belong to the history of other users
+            diesel::delete(impersonation_log::table.filter(impersonation_log::user_id.eq(id))).execute(connection)?;
+            // invitations sent by the user and the invitations the user was registered with
+            diesel::delete(invitations::table.filter(invitations::invited_by_user_id.eq(id))).execute(connection)?;
+            let invitations_of_email = invitations::table.filter(invitations::email.eq(user.email.to_lowercase()));
+            match user.tenant_id {
+                Some(tenant_id) => diesel::delete(invitations_of_email.filter(invitations::tenant_id.eq(tenant_id))).execute(connection)?,
+                None => diesel::delete(invitations_of_email.filter(invitations::tenant_id.is_null())).execute(connection)?
+            };
+            let login_attempts_of_email = login_attempts::table.filter(login_attempts::email.eq(user.email.to_lowercase()));
+            match user.tenant_id {
+                Some(tenant_id) => diesel::delete(login_attempts_of_email.filter(login_attempts::tenant_id.eq(tenant_id))).execute(connection)?,
+                None => diesel::delete(login_attempts_of_email.filter(login_attempts::tenant_id.is_null())).execute(connection)?
+            };
+            diesel::delete(users::table.find(id)).execute(connection)?;
+            Ok(())
+        }).is_ok()
+    }
+
+    /// Permanently remove the users that were soft deleted more than `grace_seconds` ago. Returns the removed users
+    pub fn purge_deleted(grace_seconds: i64, connection: &Connection) -> Vec<User> {
+        let deleted_before = SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).unwrap().as_secs() as i64 - grace_seconds;
+        let users = users::table
+            .filter(users::deleted_at.lt(deleted_before))
+            .load::<User>(connection)
+            .unwrap_or_default();
+        users.into_iter().filter(|user| User::hard_delete(user, connection)).collect()
+    }
+}
+
+/// Default lifetime of a refresh token in seconds if `refresh_token_lifetime_seconds` is not configured
+const DEFAULT_REFRESH_TOKEN_LIFETIME: i64 = 2592000;
+
+#[table_name = "refresh_tokens"]
+#[derive(AsChangeset, Queryable, Insertable, Debug, PartialEq, Clone, Default)]
+pub struct RefreshToken {
+    pub id: Option<i32>,
+    pub user_id: i32,
+    pub token: String,
+    pub expires_at: i64,
+    pub revoked: bool,
+}
+
+impl RefreshToken {
+    /// Prepare a new random refresh token for a user
+    pub fn new(user_id: i32, config: &Config) -> RefreshToken {
+        let lifetime = match config.get_int("refresh_token_lifetime_seconds") {
+            Ok(x) if x > 0 => x,
+            _ => DEFAULT_REFRESH_TOKEN_LIFETIME
+        };
+        // create an random alphanumeric token
+        let token: String = rand::thread_rng().sample_iter(&Alphanumeric).take(64).collect();
+        RefreshToken {
+            user_id,
+            token,
+            expires_at: SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).unwrap().as_secs() as i64 + lifetime,
+            ..Default::default()
+        }
+    }
+
+    /// Find a refresh token that is neither revoked nor expired
+    pub fn by_token(token: &str, connection: &Connection) -> Option<RefreshToken> {
+        let now = SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).unwrap().as_secs() as i64;
+        refresh_tokens::table
+            .filter(refresh_tokens::token.eq(token))
+            .filter(refresh_tokens::revoked.eq(false))
+            .filter(refresh_tokens::expires_at.gt(now))
+            .first::<RefreshToken>(connection).ok()
+    }
+
+    pub fn create(refresh_token: RefreshToken, connection: &Connection) -> QueryResult<RefreshToken> {
+        diesel::insert_into(refresh_tokens::table).values(&refresh_token).execute(connection)?;
+        refresh_tokens::table.filter(refresh_tokens::token.eq(&refresh_token.token)).first(connection)
+    }
+
+    /// Mark a refresh token as revoked so it can not be used again.
+    /// Returns false if the token was already revoked in the meantime
+    pub fn revoke(id: i32, connection: &Connection) -> bool {
+        match diesel::update(refresh_tokens::table.find(id).filter(refresh_tokens::revoked.eq(false)))
+            .set(refresh_tokens::revoked.eq(true))
+            .execute(connection) {
+            Ok(rows) => rows == 1,
+            Err(_) => false
+        }
+    }
+
+    /// Revoke all refresh tokens of a user
+    pub fn revoke_all(user_id: i32, connection: &Connection) -> bool {
+        diesel::update(refresh_tokens::table.filter(refresh_tokens::user_id.eq(user_id)))
+            .set(refresh_tokens::revoked.eq(true))
+            .execute(connection)
+            .is_ok()
+    }
+}
+
+#[table_name = "login_attempts"]
+#[derive(Queryable, Insertable, Debug, PartialEq, Clone, Default)]
+pub struct LoginAttempt {
+    pub id: Option<i32>,
+    pub email: String,
+    pub ip: Option<String>,
+    pub attempted_at: i64,
+    /// Tenant of the host the login was sent to. The same email address can belong to users of several tenants
+    pub tenant_id: Option<i32>,
+}
+
+impl LoginAttempt {
+    /// Login attempts for an email address on the hosts of a tenant. Attempts without a tenant are found with `None`
+    fn of_email(email: &str, tenant_id: Option<i32>) -> login_attempts::BoxedQuery<'static, <Connection as diesel::Connection>::Backend> {
+        let query = login_attempts::table.filter(login_attempts::email.eq(email.to_lowercase())).into_boxed();
+        match tenant_id {
+            Some(tenant_id) => query.filter(login_attempts::tenant_id.eq(tenant_id)),
+            None => query.filter(login_attempts::tenant_id.is_null())
+        }
+    }
+
+    /// Record a failed login attempt for an email address of a tenant
+    pub fn create(email: &str, tenant_id: Option<i32>, ip: Option<String>, connection: &Connection) -> bool {
+        let attempt = LoginAttempt {
+            // a different capitalisation of the email address must not reset the counter
+            email: email.to_lowercase(),
+            ip,
+            attempted_at: SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).unwrap().as_secs() as i64,
+            tenant_id,
+            ..Default::default()
+        };
+        diesel::insert_into(login_attempts::table).values(&attempt).execute(connection).is_ok()
+    }
+
+    /// Count the failed login attempts for an email address of a tenant within the last `window` seconds
+    pub fn count_recent(email: &str, tenant_id: Option<i32>, window: i64, connection: &Connection) -> i64 {
+        let since = SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).unwrap().as_secs() as i64 - window;
+        LoginAttempt::of_email(email, tenant_id)
+            .filter(login_attempts::attempted_at.gt(since))
+            .count()
+            .get_result(connection)
+            .unwrap_or(0)
+    }
+
+    /// Count the failed login attempts of all email addresses since a Unix timestamp. With a tenant only the attempts
+    /// of the tenant, otherwise the attempts of all tenants. Attempts are removed by a successful login, so only the
+    /// remaining ones are counted
+    pub fn count_since(since: i64, tenant_id: Option<i32>, connection: &Connection) -> QueryResult<i64> {
+        let query = login_attempts::table.filter(login_attempts::attempted_at.ge(since)).into_boxed();
+        match tenant_id {
+            Some(tenant_id) => query.filter(login_attempts::tenant_id.eq(tenant_id)).count().get_result(connection),
+            None => query.count().get_result(connection)
+        }
+    }
+
+    /// Find all recorded login attempts for an email address of a tenant
+    pub fn by_email(email: &str, tenant_id: Option<i32>, connection: &Connection) -> Vec<LoginAttempt> {
+        LoginAttempt::of_email(email, tenant_id)
+            .order(login_attempts::attempted_at.desc())
+            .load::<LoginAttempt>(connection)
+            .unwrap_or_default()
+    }
+
+    /// Remove all recorded login attempts for an email address of a tenant
+    pub fn clear(email: &str, tenant_id: Option<i32>, connection: &Connection) -> bool {
+        let attempts = login_attempts::table.filter(login_attempts::email.eq(email.to_lowercase()));
+        let result = match tenant_id {
+            Some(tenant_id) => diesel::delete(attempts.filter(login_attempts::tenant_id.eq(tenant_id))).execute(connection),
+            None => diesel::delete(attempts.filter(login_attempts::tenant_id.is_null())).execute(connection)
+        };
+        result.is_ok()
+    }
+}
+
+#[table_name = "revoked_tokens"]
+#[derive(Queryable, Insertable, Debug, PartialEq, Clone, Default)]
+pub struct RevokedToken {
+    pub jti: String,
+    pub revoked_at: i64,
+    pub expires_at: i64,
+}
+
+impl RevokedToken {
+    /// Revoke the token with the given id until it expires
+    pub fn create(jti: &str, expires_at: i64, connection: &Connection) -> bool {
+        let revoked_token = RevokedToken {
+            jti: jti.to_string(),
+            revoked_at: SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).unwrap().as_secs() as i64,
+            expires_at,
+        };
+        diesel::insert_into(revoked_tokens::table).values(&revoked_token).execute(connection).is_ok()
+    }
+
+    /// Check if the token with the given id has been revoked
+    pub fn is_revoked(jti: &str, connection: &Connection) -> bool {
+        revoked_tokens::table
+            .filter(revoked_tokens::jti.eq(jti))
+            .count()
+            .get_result::<i64>(connection)
+            .map(|count| count > 0)
+            .unwrap_or(false)
+    }
+
+    /// Remove all revoked tokens that are expired anyway
+    pub fn delete_expired(connection: &Connection) -> bool {
+        let now = SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).unwrap().as_secs() as i64;
+        diesel::delete(revoked_tokens::table.filter(revoked_tokens::expires_at.lt(now))).execute(connection).is_ok()
+    }
+}
+
+#[table_name = "sessions"]
+#[derive(Queryable, Insertable, Serialize, Debug, PartialEq, Clone, Default)]
+pub struct Session {
+    pub id: Option<i32>,
+    pub user_id: i32,
+    pub jti: String,
+    pub user_agent: Option<String>,
+    pub ip: Option<String>,
+    pub created_at: i64,
+    pub last_seen_at: i64,
+    pub revoked: bool,
+}
+
+impl Session {
+    /// Whether the user had a session from the ip address before
+    pub fn ip_seen(user_id: i32, ip: &str, connection: &Connection) -> bool {
+        sessions::table
+            .filter(sessions::user_id.eq(user_id))
+            .filter(sessions::ip.eq(ip))
+            .count()
+            .get_result::<i64>(connection)
+            .map(|count| count > 0)
+            .unwrap_or(true)
+    }
+
+    /// Start a new session for the token with the given id
+    pub fn create(user_id: i32, jti: &str, user_agent: Option<String>, ip: Option<String>, connection: &Connection) -> bool {
+        let now = SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).unwrap().as_secs() as i64;
+        let session = Session {
+            user_id,
+            jti: jti.to_string(),
+            // the user agent is truncated to the column size
+            user_agent: user_agent.map(|u| u.chars().take(512).collect()),
+            ip,
+            created_at: now,
+            last_seen_at: now,
+            ..Default::default()
+        };
+        diesel::insert_into(sessions::table).values(&session).execute(connection).is_ok()
+    }
+
+    /// Find one page of the sessions of a user that have not been revoked. Returns the page and the total number of sessions
+    pub fn by_user(user_id: i32, offset: i64, limit: i64, connection: &Connection) -> QueryResult<(Vec<Session>, i64)> {
+        let total = sessions::table
+            .filter(sessions::user_id.eq(user_id))
+            .filter(sessions::revoked.eq(false))
+            .count()
+            .get_result::<i64>(connection)?;
+        let sessions = sessions::table
+            .filter(sessions::user_id.eq(user_id))
+            .filter(sessions::revoked.eq(false))
+            .order(sessions::last_seen_at.desc())
+            .limit(limit)
+            .offset(offset)
+            .load::<Session>(connection)?;
+        Ok((sessions, total))
+    }
+
+    /// Find all sessions of a user including the revoked ones
+    pub fn all_by_user(user_id: i32, connection: &Connection) -> Vec<Session> {
+        sessions::table
+            .filter(sessions::user_id.eq(user_id))
+            .order(sessions::created_at.desc())
+            .load::<Session>(connection)
+            .unwrap_or_default()
+    }
+
+    /// Check if the session of the token with the given id has been revoked
+    pub fn is_revoked(jti: &str, connection: &Connection) -> bool {
+        sessions::table
+            .filter(sessions::jti.eq(jti))
+            .filter(sessions::revoked.eq(true))
+            .count()
+            .get_result::<i64>(connection)
+            .map(|count| count > 0)
+            .unwrap_or(false)
+    }
+
+    /// Update the time the session was last used
+    pub fn touch(jti: &str, connection: &Connection) -> bool {
+        let now = SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).unwrap().as_secs() as i64;
+        diesel::update(sessions::table.filter(sessions::jti.eq(jti))).set(sessions::last_seen_at.eq(now)).execute(connection).is_ok()
+    }
+
+    /// Revoke a single session of a user. Returns false if no such session exists
+    pub fn revoke(user_id: i32, jti: &str, connection: &Connection) -> bool {
+        match diesel::update(sessions::table.filter(sessions::user_id.eq(user_id)).filter(sessions::jti.eq(jti)))
+            .set(sessions::revoked.eq(true))
+            .execute(connection) {
+            Ok(rows) => rows == 1,
+            Err(_) => false
+        }
+    }
+
+    /// Revoke all sessions of a user
+    pub fn revoke_all(user_id: i32, connection: &Connection) -> bool {
+        diesel::update(sessions::table.filter(sessions::user_id.eq(user_id)))
+            .set(sessions::revoked.eq(true))
+            .execute(connection)
+            .is_ok()
+    }
+
+    /// Revoke all sessions of a user except the session of the token with the given id
+    pub fn revoke_all_except(user_id: i32, jti: &str, connection: &Connection) -> bool {
+        diesel::update(sessions::table.filter(sessions::user_id.eq(user_id)).filter(sessions::jti.ne(jti)))
+            .set(sessions::revoked.eq(true))
+            .execute(connection)
+            .is_ok()
+    }
+}
+
+#[table_name = "email_log"]
+#[derive(Queryable, Insertable, Serialize, Debug, PartialEq, Clone, Default)]
+pub struct EmailLog {
+    pub id: Option<i32>,
+    pub user_id: Option<i32>,
+    pub template: String,
+    pub subject: String,
+    pub sent_at: i64,
+    pub success: bool,
+    pub error_message: Option<String>,
+}
+
+impl EmailLog {
+    /// Record an attempt of sending a mail
+    pub fn create(user_id: Option<i32>, template: &str, subject: &str, error_message: Option<String>, connection: &Connection) -> bool {
+        let entry = EmailLog {
+            user_id,
+            template: template.to_string(),
+            subject: subject.to_string(),
+            sent_at: SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).unwrap().as_secs() as i64,
+            success: error_message.is_none(),
+            error_message,
+            ..Default::default()
+        };
+        diesel::insert_into(email_log::table).values(&entry).execute(connection).is_ok()
+    }
+
+    /// Find all mails that were sent to a user
+    pub fn by_user(user_id: i32, connection: &Connection) -> Vec<EmailLog> {
+        email_log::table
+            .filter(email_log::user_id.eq(user_id))
+            .order(email_log::sent_at.desc())
+            .load::<EmailLog>(connection)
+            .unwrap_or_default()
+    }
+}
+
+#[table_name = "backup_codes"]
+#[derive(Queryable, Insertable, Debug, PartialEq, Clone, Default)]
+pub struct BackupCode {
+    pub id: Option<i32>,
+    pub user_id: i32,
+    pub code_hash: String,
+    pub used_at: Option<i64>,
+}
+
+impl BackupCode {
+    /// Number of backup codes that are created for a user
+    pub const COUNT: usize = 10;
+    /// Number of characters of a backup code
+    pub const LENGTH: usize = 8;
+
+    /// Replace all backup codes of a user with new random codes. Returns the new codes in plain text.
+    /// Only the hashes are stored
+    pub fn regenerate(user_id: i32, security: &SecuritySettings, connection: &Connection) -> QueryResult<Vec<String>> {
+        let codes: Vec<String> = (0..BackupCode::COUNT)
+            .map(|_| rand::thread_rng().sample_iter(&Alphanumeric).take(BackupCode::LENGTH).collect())
+            .collect();
+        let entries: Vec<BackupCode> = codes.iter()
+            .map(|code| BackupCode { user_id, code_hash: bcrypt::hash(code, security.bcrypt_cost).unwrap(), ..Default::default() })
+            .collect();
+        connection.transaction(|| {
+            diesel::delete(backup_codes::table.filter(backup_codes::user_id.eq(user_id))).execute(connection)?;
+            diesel::insert_into(backup_codes::table).values(&entries).execute(connection)?;
+            Ok(codes)
+        })
+    }
+
+    /// Use a backup code of a user. Returns true if the code is valid and has not been used before
+    pub fn redeem(user_id: i32, code: &str, connection: &Connection) -> bool {
+        let unused = backup_codes::table
+            .filter(backup_codes::user_id.eq(user_id))
+            .filter(backup_codes::used_at.is_null())
+            .load::<BackupCode>(connection)
+            .unwrap_or_default();
+        let matching = unused.into_iter().find(|c| verify(code.trim(), &c.code_hash).unwrap_or(false));
+        match matching {
+            Some(backup_code) => {
+                let now = SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).unwrap().as_secs() as i64;
+                // the code is only marked as used if nobody else used it in the meantime
+                diesel::update(backup_codes::table.find(backup_code.id.unwrap()).filter(backup_codes::used_at.is_null()))
+                    .set(backup_codes::used_at.eq(now))
+                    .execute(connection)
+                    .map(|count| count == 1)
+                    .unwrap_or(false)
+            }
+            None => false
+        }
+    }
+
+    /// Count the unused backup codes of a user
+    pub fn count_remaining(user_id: i32, connection: &Connection) -> i64 {
+        backup_codes::table
+            .filter(backup_codes::user_id.eq(user_id))
+            .filter(backup_codes::used_at.is_null())
+            .count()
+            .get_result(connection)
+            .unwrap_or(0)
+    }
+
+    /// Remove all backup codes of a user
+    pub fn delete_for_user(user_id: i32, connection: &Connection) -> bool {
+        diesel::delete(backup_codes::table.filter(backup_codes::user_id.eq(user_id))).execute(connection).is_ok()
+    }
+}
+
+#[table_name = "phone_otps"]
+#[derive(Queryable, Insertable, Debug, PartialEq, Clone, Default)]
+pub struct PhoneOtp {
+    pub id: Option<i32>,
+    pub user_id: i32,
+    pub otp_hash: String,
+    pub expires_at: i64,
+}
+
+impl PhoneOtp {
+    /// Number of digits of an OTP
+    pub const LENGTH: usize = 6;
+    /// Seconds for which an OTP can be confirmed
+    pub const VALIDITY_SECONDS: i64 = 600;
+
+    /// Replace the OTP of a user with a new random one. Returns the OTP in plain text. Only the hash is stored
+    pub fn regenerate(user_id: i32, security: &SecuritySettings, connection: &Connection) -> QueryResult<String> {
+        let otp: String = (0..PhoneOtp::LENGTH).map(|_| rand::thread_rng().gen_range(0, 10).to_string()).collect();
+        let now = SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).unwrap().as_secs() as i64;
+        let entry = PhoneOtp { user_id, otp_hash: bcrypt::hash(&otp, security.bcrypt_cost).unwrap(), expires_at: now + PhoneOtp::VALIDITY_SECONDS, ..Default::default() };
+        connection.transaction(|| {
+            diesel::delete(phone_otps::table.filter(phone_otps::user_id.eq(user_id))).execute(connection)?;
+            diesel::insert_into(phone_otps::table).values(&entry).execute(connection)?;
+            Ok(otp)
+        })
+    }
+
+    /// Use the OTP of a user. Returns true if the OTP is valid and has not expired. The OTP expires when it is used
+    pub fn redeem(user_id: i32, otp: &str, connection: &Connection) -> bool {
+        let now = SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).unwrap().as_secs() as i64;
+        let valid = phone_otps::table
+            .filter(phone_otps::user_id.eq(user_id))
+            .filter(phone_otps::expires_at.gt(now))
+            .load::<PhoneOtp>(connection)
+            .unwrap_or_default();
+        match valid.into_iter().find(|o| verify(otp.trim(), &o.otp_hash).unwrap_or(false)) {
+            // the OTP is only expired here if nobody else used it in the meantime
+            Some(phone_otp) => diesel::update(phone_otps::table.find(phone_otp.id.unwrap()).filter(phone_otps::expires_at.gt(now)))
+                .set(phone_otps::expires_at.eq(now))
+                .execute(connection)
+                .map(|count| count == 1)
+                .unwrap_or(false),
+            None => false
+        }
+    }
+}
+
+#[table_name = "password_history"]
+#[derive(Queryable, Insertable, Debug, PartialEq, Clone, Default)]
+pub struct PasswordHistory {
+    pub id: Option<i32>,
+    pub user_id: i32,
+    pub password_hash: String,
+    pub created_at: i64,
+}
+
+impl PasswordHistory {
+    /// Remember a new password hash of a user. Only the latest `keep` entries are kept
+    pub fn record(user_id: i32, password_hash: &str, keep: usize, connection: &Connection) -> bool {
+        if keep == 0 {
+            return PasswordHistory::clear(user_id, connection);
+        }
+        let entry = PasswordHistory {
+            user_id,
+            password_hash: password_hash.to_string(),
+            created_at: SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).unwrap().as_secs() as i64,
+            ..Default::default()
+        };
+        if diesel::insert_into(password_history::table).values(&entry).execute(connection).is_err() {
+            return false;
+        }
+        // remove the entries beyond the latest ones
+        let outdated: Vec<Option<i32>> = password_history::table
+            .select(password_history::id)
+            .filter(password_history::user_id.eq(user_id))
+            .order((password_history::created_at.desc(), password_history::id.desc()))
+            .offset(keep as i64)
+            .limit(i64::from(i32::max_value()))
+            .load(connection)
+            .unwrap_or_default();
+        let outdated: Vec<i32> = outdated.into_iter().flatten().collect();
+        outdated.is_empty() || diesel::delete(password_history::table.filter(password_history::id.eq_any(outdated))).execute(connection).is_ok()
+    }
+
+    /// Check if a password is the current password of the user or one of the last `count` passwords
+    pub fn recently_used(user: &User, password: &str, count: usize, connection: &Connection) -> bool {
+        if count == 0 {
+            return false;
+        }
+        if verify(password, &user.password).unwrap_or(false) {
+            return true;
+        }
+        password_history::table
+            .filter(password_history::user_id.eq(user.id.unwrap()))
+            .order((password_history::created_at.desc(), password_history::id.desc()))
+            .limit(count as i64)
+            .load::<PasswordHistory>(connection)
+            .unwrap_or_default()
+            .iter()
+            .any(|entry| verify(password, &entry.password_hash).unwrap_or(false))
+    }
+
+    /// Remove the password history of a user
+    pub fn clear(user_id: i32, connection: &Connection) -> bool {
+        diesel::delete(password_history::table.filter(password_history::user_id.eq(user_id))).execute(connection).is_ok()
+    }
+}
+
+#[table_name = "invitations"]
+#[derive(Queryable, Insertable, Debug, PartialEq, Clone, Default)]
+pub struct Invitation {
+    pub id: Option<i32>,
+    pub email: String,
+    pub token: String,
+    pub invited_by_user_id: i32,
+    pub expires_at: i64,
+    pub accepted_at: Option<i64>,
+    /// Tenant the invited user is registered for
+    pub tenant_id: Option<i32>,
+}
+
+impl Invitation {
+    /// Prepare a new invitation with a random token for a user of the tenant
+    pub fn new(email: &str, invited_by_user_id: i32, tenant_id: Option<i32>, expires_in_seconds: i64) -> Invitation {
+        // create an random alphanumeric token
+        let token: String = rand::thread_rng().sample_iter(&Alphanumeric).take(32).collect();
+        Invitation {
+            // emails of users are stored in lowercase
+            email: email.to_lowercase(),
+            token,
+            invited_by_user_id,
+            expires_at: SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).unwrap().as_secs() as i64 + expires_in_seconds,
+            tenant_id,
+            ..Default::default()
+        }
+    }
+
+    /// Save a new invitation
+    pub fn create(invitation: Invitation, connection: &Connection) -> QueryResult<Invitation> {
+        diesel::insert_into(invitations::table).values(&invitation).execute(connection)?;
+        invitations::table.filter(invitations::token.eq(&invitation.token)).first::<Invitation>(connection)
+    }
+
+    /// Find an invitation for an email address of a tenant that is neither accepted nor expired. Invitations without
+    /// a tenant are found with `None`
+    pub fn valid(token: &str, email: &str, tenant_id: Option<i32>, connection: &Connection) -> Option<Invitation> {
+        let now = SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).unwrap().as_secs() as i64;
+        let query = invitations::table
+            .filter(invitations::token.eq(token))
+            .filter(invitations::email.eq(email.to_lowercase()))
+            .filter(invitations::accepted_at.is_null())
+            .filter(invitations::expires_at.gt(now))
+            .into_boxed();
+        let query = match tenant_id {
+            Some(tenant_id) => query.filter(invitations::tenant_id.eq(tenant_id)),
+            None => query.filter(invitations::tenant_id.is_null())
+        };
+        query.first::<Invitation>(connection).ok()
+    }
+
+    /// Mark an invitation as accepted. Returns true if the invitation has not been accepted before
+    pub fn accept(id: i32, connection: &Connection) -> bool {
+        let now = SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).unwrap().as_secs() as i64;
+        diesel::update(invitations::table.find(id).filter(invitations::accepted_at.is_null()))
+            .set(invitations::accepted_at.eq(now))
+            .execute(connection)
+            .map(|count| count == 1)
+            .unwrap_or(false)
+    }
+}
+
+#[table_name = "impersonation_log"]
+#[derive(Queryable, Insertable, Serialize, Debug, PartialEq, Clone, Default)]
+pub struct ImpersonationLog {
+    pub id: Option<i32>,
+    pub admin_id: i32,
+    pub user_id: i32,
+    pub jti: String,
+    pub created_at: i64,
+    pub expires_at: i64,
+}
+
+impl ImpersonationLog {
+    /// Record that an admin started impersonating a user
+    pub fn create(admin_id: i32, user_id: i32, jti: &str, expires_at: i64, connection: &Connection) -> bool {
+        let entry = ImpersonationLog {
+            admin_id,
+            user_id,
+            jti: jti.to_string(),
+            created_at: SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).unwrap().as_secs() as i64,
+            expires_at,
+            ..Default::default()
+        };
+        diesel::insert_into(impersonation_log::table).values(&entry).execute(connection).is_ok()
+    }
+
+    /// Find all impersonations whose token has neither expired nor been revoked. With a tenant only the
+    /// impersonations of the users of the tenant
+    pub fn active(tenant_id: Option<i32>, connection: &Connection) -> Vec<ImpersonationLog> {
+        let now = SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).unwrap().as_secs() as i64;
+        let mut query = impersonation_log::table.filter(impersonation_log::expires_at.gt(now)).into_boxed();
+        if let Some(tenant_id) = tenant_id {
+            query = query.filter(impersonation_log::user_id.nullable().eq_any(users::table.select(users::id).filter(users::tenant_id.eq(tenant_id))));
+        }
+        query
+            .order(impersonation_log::created_at.desc())
+            .load::<ImpersonationLog>(connection)
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|entry| !Session::is_revoked(&entry.jti, connection) && !RevokedToken::is_revoked(&entry.jti, connection))
+            .collect()
+    }
+}
+
+#[table_name = "api_keys"]
+#[derive(Queryable, Insertable, Debug, PartialEq, Clone, Default)]
+pub struct ApiKey {
+    pub id: Option<i32>,
+    pub user_id: i32,
+    pub key_prefix: String,
+    pub key_hash: String,
+    pub name: String,
+    pub last_used_at: Option<i64>,
+    pub created_at: i64,
+    pub revoked: bool,
+}
+
+impl ApiKey {
+    /// Length of a generated api key
+    pub const LENGTH: usize = 40;
+    /// Length of the prefix that identifies an api key. It is stored in plain text for finding the key
+    pub const PREFIX_LENGTH: usize = 8;
+
+    /// Prepare a new api key for a user. Returns the api key and the plain text key that is shown once
+    pub fn generate(user_id: i32, name: &str, security: &SecuritySettings) -> (ApiKey, String) {
+        // create an random alphanumeric key
+        let key: String = rand::thread_rng().sample_iter(&Alphanumeric).take(ApiKey::LENGTH).collect();
+        let api_key = ApiKey {
+            user_id,
+            key_prefix: key[..ApiKey::PREFIX_LENGTH].to_string(),
+            key_hash: bcrypt::hash(&key, security.bcrypt_cost).unwrap(),
+            name: name.to_string(),
+            created_at: SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).unwrap().as_secs() as i64,
+            ..Default::default()
+        };
+        (api_key, key)
+    }
+
+    /// Save a new api key
+    pub fn create(api_key: ApiKey, connection: &Connection) -> QueryResult<ApiKey> {
+        diesel::insert_into(api_keys::table).values(&api_key).execute(connection)?;
+        api_keys::table.filter(api_keys::key_hash.eq(&api_key.key_hash)).first::<ApiKey>(connection)
+    }
+
+    /// Find one page of the api keys of a user that have not been revoked. Returns the page and the total number of keys
+    pub fn by_user(user_id: i32, offset: i64, limit: i64, connection: &Connection) -> QueryResult<(Vec<ApiKey>, i64)> {
+        let total = api_keys::table
+            .filter(api_keys::user_id.eq(user_id))
+            .filter(api_keys::revoked.eq(false))
+            .count()
+            .get_result::<i64>(connection)?;
+        let api_keys = api_keys::table
+            .filter(api_keys::user_id.eq(user_id))
+            .filter(api_keys::revoked.eq(false))
+            .order(api_keys::name)
+            .limit(limit)
+            .offset(offset)
+            .load::<ApiKey>(connection)?;
+        Ok((api_keys, total))
+    }
+
+    /// Find the user of an api key. Revoked keys are not accepted. The time the key was used is updated
+    pub fn authenticate(key: &str, connection: &Connection) -> Option<i32> {
+        if key.len() != ApiKey::LENGTH || !key.is_char_boundary(ApiKey::PREFIX_LENGTH) {
+            return None;
+        }
+        let api_key = api_keys::table
+            .filter(api_keys::key_prefix.eq(&key[..ApiKey::PREFIX_LENGTH]))
+            .filter(api_keys::revoked.eq(false))
+            .load::<ApiKey>(connection)
+            .unwrap_or_default()
+            .into_iter()
+            .find(|api_key| verify(key, &api_key.key_hash).unwrap_or(false))?;
+        let now = SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).unwrap().as_secs() as i64;
+        let _ = diesel::update(api_keys::table.find(api_key.id.unwrap())).set(api_keys::last_used_at.eq(now)).execute(connection);
+        Some(api_key.user_id)
+    }
+
+    /// Revoke an api key of a user. Returns true if a key was revoked
+    pub fn revoke(user_id: i32, id: i32, connection: &Connection) -> bool {
+        diesel::update(api_keys::table.find(id).filter(api_keys::user_id.eq(user_id)).filter(api_keys::revoked.eq(false)))
+            .set(api_keys::revoked.eq(true))
+            .execute(connection)
+            .map(|count| count == 1)
+            .unwrap_or(false)
+    }
+}
+
+#[table_name = "webhooks"]
+#[derive(Queryable, Insertable, Serialize, Debug, PartialEq, Clone, Default)]
+pub struct Webhook {
+    pub id: Option<i32>,
+    pub url: String,
+    /// Key for signing the payloads. Is only returned when the webhook is created
+    #[serde(skip_serializing)]
+    pub secret: String,
+    /// Comma separated list of the events the webhook receives
+    pub events: String,
+    pub created_by_user_id: i32,
+    pub active: bool,
+    /// Tenant whose users the webhook receives events for. Webhooks without a tenant receive the events of all users
+    pub tenant_id: Option<i32>,
+}
+
+impl Webhook {
+    /// Number of characters of a generated secret
+    pub const SECRET_LENGTH: usize = 32;
+
+    /// Prepare a new webhook of a tenant with a random secret
+    pub fn generate(url: &str, events: &[String], created_by_user_id: i32, tenant_id: Option<i32>) -> Webhook {
+        Webhook {
+            url: url.to_string(),
+            secret: rand::thread_rng().sample_iter(&Alphanumeric).take(Webhook::SECRET_LENGTH).collect(),
+            events: events.join(","),
+            created_by_user_id,
+            active: true,
+            tenant_id,
+            ..Default::default()
+        }
+    }
+
+    /// Store a new webhook
+    pub fn create(webhook: Webhook, connection: &Connection) -> QueryResult<Webhook> {
+        diesel::insert_into(webhooks::table).values(&webhook).execute(connection)?;
+        webhooks::table.order(webhooks::id.desc()).first::<Webhook>(connection)
+    }
+
+    /// Remove a webhook. Admins of a tenant can only remove the webhooks of their tenant, admins without a tenant
+    /// every webhook. Returns false if there is no such webhook
+    pub fn delete(id: i32, tenant_id: Option<i32>, connection: &Connection) -> bool {
+        let result = match tenant_id {
+            Some(tenant_id) => diesel::delete(webhooks::table.find(id).filter(webhooks::tenant_id.eq(tenant_id))).execute(connection),
+            None => diesel::delete(webhooks::table.find(id)).execute(connection)
+        };
+        match result {
+            Ok(rows) => rows == 1,
+            Err(_) => false
+        }
+    }
+
+    /// Find all active webhooks that receive the event for a user of the tenant: the webhooks of the tenant and the
+    /// webhooks without a tenant
+    pub fn for_event(event: &str, tenant_id: Option<i32>, connection: &Connection) -> Vec<Webhook> {
+        let query = webhooks::table.filter(webhooks::active.eq(true)).into_boxed();
+        let query = match tenant_id {
+            Some(tenant_id) => query.filter(webhooks::tenant_id.eq(tenant_id).or(webhooks::tenant_id.is_null())),
+            None => query.filter(webhooks::tenant_id.is_null())
+        };
+        query
+            .load::<Webhook>(connection)
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|webhook| webhook.events.split(',').any(|e| e.trim() == event))
+            .collect()
+    }
+}
+
+#[table_name = "webhook_deliveries"]
+#[derive(Queryable, Insertable, Serialize, Debug, PartialEq, Clone, Default)]
+pub struct WebhookDelivery {
+    pub id: Option<i32>,
+    pub webhook_id: i32,
+    pub event: String,
+    /// Number of the attempt starting at 0
+    pub attempt: i32,
+    /// HTTP status of the response. Is empty if the request failed
+    pub status_code: Option<i32>,
+    pub error_message: Option<String>,
+    pub delivered_at: i64,
+}
+
+impl WebhookDelivery {
+    /// Record an attempt of delivering an event
+    pub fn create(webhook_id: i32, event: &str, attempt: i32, status_code: Option<i32>, error_message: Option<String>, connection: &Connection) -> bool {
+        let entry = WebhookDelivery {
+            webhook_id,
+            event: event.to_string(),
+            attempt,
+            status_code,
+            error_message,
+            delivered_at: SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).unwrap().as_secs() as i64,
+            ..Default::default()
+        };
+        diesel::insert_into(webhook_deliveries::table).values(&entry).execute(connection).is_ok()
+    }
+}
+
+#[table_name = "audit_log"]
+#[derive(Queryable, Insertable, Serialize, Debug, PartialEq, Clone, Default)]
+pub struct AuditLog {
+    pub id: Option<i32>,
+    /// User that was changed
+    pub user_id: i32,
+    /// User that made the change. Is empty for unauthenticated requests
+    pub actor_id: Option<i32>,
+    pub action: String,
+    /// JSON object with the changed fields before the change
+    pub old_value: String,
+    /// JSON object with the changed fields after the change
+    pub new_value: String,
+    pub ip: Option<String>,
+    pub created_at: i64,
+}
+
+impl AuditLog {
+    /// Record a change of a user
+    pub fn create(user_id: i32, actor_id: Option<i32>, action: &str, old_value: String, new_value: String, ip: Option<String>, connection: &Connection) -> bool {
+        let entry = AuditLog {
+            user_id,
+            actor_id,
+            action: action.to_string(),
+            old_value,
+            new_value,
+            ip,
+            created_at: SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).unwrap().as_secs() as i64,
+            ..Default::default()
+        };
+        diesel::insert_into(audit_log::table).values(&entry).execute(connection).is_ok()
+    }
+
+    /// Find one page of the audit log, newest first. With a tenant only the entries of the users of the tenant.
+    /// Returns the page and the total number of entries
+    pub fn page(user_id: Option<i32>, tenant_id: Option<i32>, offset: i64, limit: i64, connection: &Connection) -> QueryResult<(Vec<AuditLog>, i64)> {
+        let mut count_query = audit_log::table.into_boxed();
+        let mut query = audit_log::table.into_boxed();
+        if let Some(tenant_id) = tenant_id {
+            count_query = count_query.filter(audit_log::user_id.nullable().eq_any(users::table.select(users::id).filter(users::tenant_id.eq(tenant_id))));
+            query = query.filter(audit_log::user_id.nullable().eq_any(users::table.select(users::id).filter(users::tenant_id.eq(tenant_id))));
+        }
+        if let Some(user_id) = user_id {
+            count_query = count_query.filter(audit_log::user_id.eq(user_id));
+            query = query.filter(audit_log::user_id.eq(user_id));
+        }
+        let total = count_query.count().get_result::<i64>(connection)?;
+        let entries = query.order(audit_log::id.desc()).offset(offset).limit(limit).load::<AuditLog>(connection)?;
+        Ok((entries, total))
+    }
+
+    /// Find the latest entries of a user, newest first. `from` only returns entries created at or after this time
+    pub fn for_user(user_id: i32, from: Option<i64>, limit: i64, connection: &Connection) -> QueryResult<Vec<AuditLog>> {
+        let mut query = audit_log::table.filter(audit_log::user_id.eq(user_id)).into_boxed();
+        if let Some(from) = from {
+            query = query.filter(audit_log::created_at.ge(from));
+        }
+        query.order(audit_log::id.desc()).limit(limit).load::<AuditLog>(connection)
+    }
+}
+
+#[table_name = "oauth_accounts"]
+#[changeset_options(treat_none_as_null = "true")]
+#[derive(Queryable, Insertable, AsChangeset, Debug, PartialEq, Clone, Default)]
+pub struct OAuthAccount {
+    pub id: Option<i32>,
+    pub user_id: i32,
+    pub provider: String,
+    /// Id of the account at the provider
+    pub provider_sub: String,
+    pub access_token_encrypted: Option<String>,
+    pub refresh_token_encrypted: Option<String>,
+    pub expires_at: Option<i64>,
+    pub created_at: i64,
+}
+
+impl OAuthAccount {
+    /// Prepare the link of an account of a provider to a user. The tokens are encrypted like the TOTP secrets
+    pub fn generate(user_id: i32, provider: &str, provider_sub: &str, tokens: &OAuthTokens, config: &Config) -> Result<OAuthAccount, String> {
+        let mut account = OAuthAccount {
+            user_id,
+            provider: provider.to_string(),
+            provider_sub: provider_sub.to_string(),
+            created_at: SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).unwrap().as_secs() as i64,
+            ..Default::default()
+        };
+        account.set_tokens(tokens, config)?;
+        Ok(account)
+    }
+
+    /// Replace the stored tokens with newly issued ones
+    pub fn set_tokens(&mut self, tokens: &OAuthTokens, config: &Config) -> Result<(), String> {
+        self.access_token_encrypted = Some(totp::encrypt_secret(tokens.access_token.as_bytes(), config)?);
+        self.refresh_token_encrypted = match &tokens.refresh_token {
+            Some(refresh_token) => Some(totp::encrypt_secret(refresh_token.as_bytes(), config)?),
+            // providers only issue a refresh token on the first authorization, so the stored one is kept
+            None => self.refresh_token_encrypted.clone()
+        };
+        self.expires_at = tokens.expires_at;
+        Ok(())
+    }
+
+    /// Save a new link. Fails if the account is already linked because of the unique index on provider and id
+    pub fn create(account: OAuthAccount, connection: &Connection) -> QueryResult<OAuthAccount> {
+        diesel::insert_into(oauth_accounts::table).values(&account).execute(connection)?;
+        oauth_accounts::table
+            .filter(oauth_accounts::provider.eq(&account.provider))
+            .filter(oauth_accounts::provider_sub.eq(&account.provider_sub))
+            .first::<OAuthAccount>(connection)
+    }
+
+    /// Save the changed tokens of a link
+    pub fn update(account: &OAuthAccount, connection: &Connection) -> bool {
+        match account.id {
+            Some(id) => diesel::update(oauth_accounts::table.find(id)).set(account).execute(connection).is_ok(),
+            None => false
+        }
+    }
+
+    /// Find the link of an account of a provider
+    pub fn by_provider_sub(provider: &str, provider_sub: &str, connection: &Connection) -> Option<OAuthAccount> {
+        oauth_accounts::table
+            .filter(oauth_accounts::provider.eq(provider))
+            .filter(oauth_accounts::provider_sub.eq(provider_sub))
+            .first::<OAuthAccount>(connection)
+            .ok()
+    }
+
+    /// All accounts that are linked to a user
+    pub fn by_user(user_id: i32, connection: &Connection) -> Vec<OAuthAccount> {
+        oauth_accounts::table
+            .filter(oauth_accounts::user_id.eq(user_id))
+            .order(oauth_accounts::provider)
+            .load::<OAuthAccount>(connection)
+            .unwrap_or_default()
+    }
+
+    /// Remove the link of a provider from a user. Returns false if the user has no account of the provider
+    pub fn delete(user_id: i32, provider: &str, connection: &Connection) -> bool {
+        match diesel::delete(oauth_accounts::table.filter(oauth_accounts::user_id.eq(user_id)).filter(oauth_accounts::provider.eq(provider))).execute(connection) {
+            Ok(count) => count > 0,
+            Err(_) => false
+        }
+    }
+}
+
+#[table_name = "notifications"]
+#[derive(Queryable, Insertable, Debug, PartialEq, Clone, Default)]
+pub struct Notification {
+    pub id: Option<i32>,
+    pub user_id: i32,
+    /// Kind of the notification, e.g. `password_changed`
+    pub notification_type: String,
+    /// JSON object with the details of the notification
+    pub payload: String,
+    pub read_at: Option<i64>,
+    pub created_at: i64,
+}
+
+impl Notification {
+    /// Save a new notification for a user
+    pub fn create(user_id: i32, notification_type: &str, payload: String, connection: &Connection) -> bool {
+        let notification = Notification {
+            user_id,
+            notification_type: notification_type.to_string(),
+            payload,
+            created_at: SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).unwrap().as_secs() as i64,
+            ..Default::default()
+        };
+        diesel::insert_into(notifications::table).values(&notification).execute(connection).is_ok()
+    }
+
+    /// Find one page of the notifications of a user, newest first. Returns the page and the total number of notifications
+    pub fn by_user(user_id: i32, unread_only: bool, offset: i64, limit: i64, connection: &Connection) -> QueryResult<(Vec<Notification>, i64)> {
+        let mut count_query = notifications::table.filter(notifications::user_id.eq(user_id)).into_boxed();
+        let mut query = notifications::table.filter(notifications::user_id.eq(user_id)).into_boxed();
+        if unread_only {
+            count_query = count_query.filter(notifications::read_at.is_null());
+            query = query.filter(notifications::read_at.is_null());
+        }
+        let total = count_query.count().get_result::<i64>(connection)?;
+        let notifications = query.order(notifications::id.desc()).offset(offset).limit(limit).load::<Notification>(connection)?;
+        Ok((notifications, total))
+    }
+
+    /// Mark a notification of a user as read. Notifications that were read before keep their time.
+    /// Returns false if the user has no notification with the id
+    pub fn mark_read(user_id: i32, id: i32, connection: &Connection) -> bool {
+        let notification = match notifications::table.find(id).filter(notifications::user_id.eq(user_id)).first::<Notification>(connection) {
+            Ok(notification) => notification,
+            Err(_) => return false
+        };
+        if notification.read_at.is_some() {
+            return true;
+        }
+        let now = SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).unwrap().as_secs() as i64;
+        diesel::update(notifications::table.find(id)).set(notifications::read_at.eq(now)).execute(connection).is_ok()
+    }
+
+    /// Mark all unread notifications of a user as read. Returns the number of changed notifications
+    pub fn mark_all_read(user_id: i32, connection: &Connection) -> QueryResult<usize> {
+        let now = SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).unwrap().as_secs() as i64;
+        diesel::update(notifications::table.filter(notifications::user_id.eq(user_id)).filter(notifications::read_at.is_null()))
+            .set(notifications::read_at.eq(now))
+            .execute(connection)
+    }
+}
+
+#[table_name = "email_preferences"]
+#[derive(Queryable, Insertable, Debug, PartialEq, Clone, Default)]
+pub struct EmailPreference {
+    pub user_id: i32,
+    /// Name of the mail template the preference is for
+    pub email_type: String,
+    pub subscribed: bool,
+}
+
+impl EmailPreference {
+    /// Whether the user has unsubscribed from the mails of the template. Users without a preference are subscribed
+    pub fn is_unsubscribed(user_id: i32, email_type: &str, connection: &Connection) -> bool {
+        email_preferences::table
+            .find((user_id, email_type))
+            .select(email_preferences::subscribed)
+            .first::<bool>(connection)
+            .map(|subscribed| !subscribed)
+            .unwrap_or(false)
+    }
+
+    /// Unsubscribe a user from the mails of the template
+    pub fn unsubscribe(user_id: i32, email_type: &str, connection: &Connection) -> bool {
+        let updated = diesel::update(email_preferences::table.find((user_id, email_type)))
+            .set(email_preferences::subscribed.eq(false))
+            .execute(connection);
+        match updated {
+            Ok(0) => {
+                let preference = EmailPreference { user_id, email_type: email_type.to_string(), subscribed: false };
+                diesel::insert_into(email_preferences::table).values(&preference).execute(connection).is_ok()
+            }
+            Ok(_) => true,
+            Err(_) => false
+        }
+    }
+}
+
+#[table_name = "tenants"]
+#[derive(Queryable, Insertable, Serialize, Debug, PartialEq, Clone, Default)]
+pub struct Tenant {
+    pub id: Option<i32>,
+    pub name: String,
+    /// Subdomain of the tenant, e.g. `acme` for `acme.example.com`
+    pub slug: String,
+    pub created_at: i64,
+}
+
+impl Tenant {
+    /// Save a new tenant and return it with its id
+    pub fn create(name: &str, slug: &str, connection: &Connection) -> QueryResult<Tenant> {
+        let tenant = Tenant {
+            name: name.to_string(),
+            slug: slug.to_string(),
+            created_at: SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).unwrap().as_secs() as i64,
+            ..Default::default()
+        };
+        diesel::insert_into(tenants::table).values(&tenant).execute(connection)?;
+        tenants::table.filter(tenants::slug.eq(slug)).first::<Tenant>(connection)
+    }
+
+    /// Find a tenant by its slug
+    pub fn by_slug(slug: &str, connection: &Connection) -> Option<Tenant> {
+        tenants::table.filter(tenants::slug.eq(slug)).first::<Tenant>(connection).ok()
+    }
+
+    /// Number of users of a tenant, including soft deleted users
+    pub fn count_users(id: i32, connection: &Connection) -> QueryResult<i64> {
+        users::table.filter(users::tenant_id.eq(id)).count().get_result::<i64>(connection)
+    }
+
+    /// Delete a tenant. Returns false if there is no tenant with the id
+    pub fn delete(id: i32, connection: &Connection) -> bool {
+        diesel::delete(tenants::table.find(id)).execute(connection).map(|count| count == 1).unwrap_or(false)
+    }
+}
+
+#[table_name = "feedback"]
+#[derive(Queryable, Insertable, Serialize, Debug, PartialEq, Clone, Default)]
+pub struct Feedback {
+    pub id: Option<i32>,
+    pub user_id: i32,
+    /// `bug`, `feature` or `other`
+    pub category: String,
+    pub message: String,
+    pub created_at: i64,
+}
+
+impl Feedback {
+    /// Save feedback of a user
+    pub fn create(user_id: i32, category: &str, message: &str, connection: &Connection) -> bool {
+        let entry = Feedback {
+            user_id,
+            category: category.to_string(),
+            message: message.to_string(),
+            created_at: SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).unwrap().as_secs() as i64,
+            ..Default::default()
+        };
+        diesel::insert_into(feedback::table).values(&entry).execute(connection).is_ok()
+    }
+
+    /// Find one page of the feedback, newest first. With a tenant only the feedback of the users of the tenant.
+    /// Returns the page and the total number of entries
+    pub fn list(tenant_id: Option<i32>, offset: i64, limit: i64, connection: &Connection) -> QueryResult<(Vec<Feedback>, i64)> {
+        let mut count_query = feedback::table.into_boxed();
+        let mut query = feedback::table.into_boxed();
+        if let Some(tenant_id) = tenant_id {
+            count_query = count_query.filter(feedback::user_id.nullable().eq_any(users::table.select(users::id).filter(users::tenant_id.eq(tenant_id))));
+            query = query.filter(feedback::user_id.nullable().eq_any(users::table.select(users::id).filter(users::tenant_id.eq(tenant_id))));
+        }
+        let total = count_query.count().get_result::<i64>(connection)?;
+        let entries = query.order(feedback::id.desc()).offset(offset).limit(limit).load::<Feedback>(connection)?;
+        Ok((entries, total))
+    }
+
+    /// All feedback of a user, oldest first
+    pub fn by_user(user_id: i32, connection: &Connection) -> Vec<Feedback> {
+        feedback::table.filter(feedback::user_id.eq(user_id)).order(feedback::id).load::<Feedback>(connection).unwrap_or_default()
+    }
+}
@@ -0,0 +1,106 @@
+//! Login with Google using OAuth2 with PKCE. Accounts of the providers are linked to local users in `oauth_accounts`
+use config::Config;
+use oauth2::basic::BasicClient;
+use oauth2::reqwest::http_client;
+use std::time::SystemTime;
+
+use oauth2::{AuthUrl, AuthorizationCode, ClientId, ClientSecret, CsrfToken, PkceCodeChallenge, PkceCodeVerifier, RedirectUrl, Scope, TokenResponse, TokenUrl};
+
+/// Authorization endpoint of Google
+const GOOGLE_AUTH_URL: &str = "https://accounts.google.com/o/oauth2/v2/auth";
+/// Token endpoint of Google
+const GOOGLE_TOKEN_URL: &str = "https://oauth2.googleapis.com/token";
+/// Endpoint that returns the profile of the user a token was issued for
+const GOOGLE_USERINFO_URL: &str = "https://openidconnect.googleapis.com/v1/userinfo";
+
+/// OAuth providers whose accounts can be linked to a user
+pub const PROVIDERS: [&str; 1] = ["google"];
+
+/// Tokens that a provider issued for an account. They are only stored encrypted
+#[derive(Debug, Clone, Default)]
+pub struct OAuthTokens {
+    pub access_token: String,
+    pub refresh_token: Option<String>,
+    /// Unix timestamp at which the access token expires
+    pub expires_at: Option<i64>,
+}
+
+/// Profile of a Google account as returned by the userinfo endpoint
+#[derive(Deserialize, Debug, Clone)]
+pub struct GoogleUser {
+    /// Unique and stable id of the Google account
+    pub sub: String,
+    pub email: String,
+    #[serde(default)]
+    pub email_verified: bool,
+    pub given_name: Option<String>,
+    pub family_name: Option<String>,
+}
+
+/// Create the OAuth2 client from the `[google_oauth]` section of the configuration file
+fn google_client(config: &Config) -> Result<BasicClient, String> {
+    let client_id = config.get_str("google_oauth.client_id").map_err(|_| "Google client id missing".to_string())?;
+    let client_secret = config.get_str("google_oauth.client_secret").map_err(|_| "Google client secret missing".to_string())?;
+    let redirect_url = config.get_str("google_oauth.redirect_url").map_err(|_| "Google redirect url missing".to_string())?;
+    Ok(BasicClient::new(
+        ClientId::new(client_id),
+        Some(ClientSecret::new(client_secret)),
+        AuthUrl::new(GOOGLE_AUTH_URL.to_string()).map_err(|e| e.to_string())?,
+        Some(TokenUrl::new(GOOGLE_TOKEN_URL.to_string()).map_err(|e| e.to_string())?),
+    ).set_redirect_url(RedirectUrl::new(redirect_url).map_err(|e| e.to_string())?))
+}
+
+/// Build the url of Google's authorization endpoint.
+/// Returns the url, the random state and the PKCE verifier that is needed for exchanging the code
+pub fn authorize_url(config: &Config) -> Result<(String, String, String), String> {
+    let client = google_client(config)?;
+    let (pkce_challenge, pkce_verifier) = PkceCodeChallenge::new_random_sha256();
+    let (url, state) = client
+        .authorize_url(CsrfToken::new_random)
+        .add_scope(Scope::new("openid".to_string()))
+        .add_scope(Scope::new("email".to_string()))
+        .add_scope(Scope::new("profile".to_string()))
+        .set_pkce_challenge(pkce_challenge)
+        .url();
+    Ok((url.to_string(), state.secret().clone(), pkce_verifier.secret().clone()))
+}
+
+/// Exchange an authorization code for an access token and read the profile of the Google account with it.
+/// Returns the profile and the issued tokens
+pub fn google_user(code: &str, pkce_verifier: &str, config: &Config) -> Result<(GoogleUser, OAuthTokens), String> {
+    let client = google_client(config)?;
+    let token = client
+        .exchange_code(AuthorizationCode::new(code.to_string()))
+        .set_pkce_verifier(PkceCodeVerifier::new(pkce_verifier.to_string()))
+        .request(http_client)
+        .map_err(|e| format!("Authorization code could not be exchanged: {}", e))?;
+    let google_user = reqwest::blocking::Client::new()
+        .get(GOOGLE_USERINFO_URL)
+        .bearer_auth(token.access_token().secret())
+        .send()
+        .and_then(|response| response.error_for_status())
+        .and_then(|response| response.json::<GoogleUser>())
+        .map_err(|e| format!("Google profile could not be read: {}", e))?;
+    let now = SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).unwrap().as_secs() as i64;
+    let tokens = OAuthTokens {
+        access_token: token.access_token().secret().clone(),
+        refresh_token: token.refresh_token().map(|refresh_token| refresh_token.secret().clone()),
+        expires_at: token.expires_in().map(|expires_in| now + expires_in.as_secs() as i64),
+    };
+    Ok((google_user, tokens))
+}
+
+/// Exchange an authorization code of a provider and return the id of the account and the issued tokens
+pub fn provider_account(provider: &str, code: &str, pkce_verifier: &str, config: &Config) -> Result<(String, OAuthTokens), String> {
+    match provider {
+        "google" => {
+            let (google_user, tokens) = google_user(code, pkce_verifier, config)?;
+            // only verified email addresses may be linked to local users
+            if !google_user.email_verified {
+                return Err("The email address of the Google account is not verified".to_string());
+            }
+            Ok((google_user.sub, tokens))
+        }
+        _ => Err(format!("Unsupported provider {}", provider))
+    }
+}
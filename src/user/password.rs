@@ -0,0 +1,53 @@
+use crate::ErrorCode;
+
+/// Requirements for passwords. Read once at startup and stored in Rocket's managed state
+#[derive(Debug, Serialize)]
+pub struct PasswordPolicy {
+    /// Minimum number of characters
+    pub min_length: usize,
+    /// At least one uppercase letter is required
+    pub require_uppercase: bool,
+    /// At least one digit is required
+    pub require_digit: bool,
+    /// At least one character that is neither a letter nor a digit is required
+    pub require_special: bool,
+}
+
+/// Default minimum password length if `password.min_length` is not configured
+const DEFAULT_MIN_LENGTH: usize = 8;
+
+impl PasswordPolicy {
+    /// Read the `[password]` settings from the configuration. Panics on invalid values
+    pub fn from_config() -> PasswordPolicy {
+        let configuration = crate::load_config().ok();
+        let min_length = match configuration.as_ref().and_then(|c| c.get_int("password.min_length").ok()) {
+            Some(length) if length >= 1 => length as usize,
+            Some(length) => panic!("password.min_length has to be at least 1 but is {}", length),
+            None => DEFAULT_MIN_LENGTH
+        };
+        let flag = |key: &str| configuration.as_ref().and_then(|c| c.get_bool(key).ok()).unwrap_or(false);
+        PasswordPolicy {
+            min_length,
+            require_uppercase: flag("password.require_uppercase"),
+            require_digit: flag("password.require_digit"),
+            require_special: flag("password.require_special"),
+        }
+    }
+
+    /// Check a password against the policy. Returns the error code and a message describing the first unmet requirement
+    pub fn validate(&self, password: &str) -> Result<(), (ErrorCode, String)> {
+        if password.chars().count() < self.min_length {
+            return Err((ErrorCode::PasswordTooShort, format!("Password is too short. Minimum {} characters!", self.min_length)));
+        }
+        if self.require_uppercase && !password.chars().any(char::is_uppercase) {
+            return Err((ErrorCode::PasswordTooWeak, String::from("Password must contain an uppercase letter")));
+        }
+        if self.require_digit && !password.chars().any(|c| c.is_ascii_digit()) {
+            return Err((ErrorCode::PasswordTooWeak, String::from("Password must contain a digit")));
+        }
+        if self.require_special && password.chars().all(char::is_alphanumeric) {
+            return Err((ErrorCode::PasswordTooWeak, String::from("Password must contain a special character")));
+        }
+        Ok(())
+    }
+}
\ No newline at end of file
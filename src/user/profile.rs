@@ -0,0 +1,45 @@
+use regex::Regex;
+
+use crate::ErrorCode;
+
+/// Maximum number of characters of a bio
+pub const MAX_BIO_LENGTH: usize = 500;
+/// Length of the `website` column
+const MAX_WEBSITE_LENGTH: usize = 2048;
+/// An http or https URL with a host and without whitespace
+const WEBSITE_PATTERN: &str = r"^https?://[^\s/?#]+[^\s]*$";
+/// Length of the `avatar_url` column
+const MAX_AVATAR_URL_LENGTH: usize = 2048;
+/// An https URL with a host and without whitespace
+const AVATAR_URL_PATTERN: &str = r"^https://[^\s/?#]+[^\s]*$";
+
+/// Check that a bio is not longer than `MAX_BIO_LENGTH` characters
+pub fn validate_bio(bio: &str) -> Result<(), (ErrorCode, String)> {
+    if bio.chars().count() > MAX_BIO_LENGTH {
+        return Err((ErrorCode::BioTooLong, format!("Bio is too long. Maximum {} characters!", MAX_BIO_LENGTH)));
+    }
+    Ok(())
+}
+
+/// Check that a website is an http or https URL that fits into the database column
+pub fn validate_website(website: &str) -> Result<(), (ErrorCode, String)> {
+    if website.len() > MAX_WEBSITE_LENGTH {
+        return Err((ErrorCode::InvalidWebsite, format!("Website is too long. Maximum {} characters!", MAX_WEBSITE_LENGTH)));
+    }
+    if !Regex::new(WEBSITE_PATTERN).unwrap().is_match(website) {
+        return Err((ErrorCode::InvalidWebsite, String::from("Website has to be a URL starting with http:// or https://")));
+    }
+    Ok(())
+}
+
+/// Check that an avatar URL is an https URL that fits into the database column. Plain http is rejected, so the
+/// image does not cause mixed content warnings in clients
+pub fn validate_avatar_url(url: &str) -> Result<(), (ErrorCode, String)> {
+    if url.len() > MAX_AVATAR_URL_LENGTH {
+        return Err((ErrorCode::InvalidAvatarUrl, format!("Avatar URL is too long. Maximum {} characters!", MAX_AVATAR_URL_LENGTH)));
+    }
+    if !Regex::new(AVATAR_URL_PATTERN).unwrap().is_match(url) {
+        return Err((ErrorCode::InvalidAvatarUrl, String::from("Avatar URL has to be a URL starting with https://")));
+    }
+    Ok(())
+}
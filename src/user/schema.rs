@@ -8,7 +8,245 @@ table! {
         registration_code -> Nullable<Varchar>,
         reset_code -> Nullable<Varchar>,
         image -> Nullable<Mediumblob>,
-        create_date -> Unsigned<Bigint>,
-        edit_date -> Unsigned<Bigint>,
+        create_date -> Bigint,
+        edit_date -> Bigint,
+        role -> Varchar,
+        deleted_at -> Nullable<Bigint>,
+        reset_code_expires_at -> Nullable<Bigint>,
+        registration_code_expires_at -> Nullable<Bigint>,
+        lastname -> Nullable<Varchar>,
+        phone -> Nullable<Varchar>,
+        phone_verified -> Bool,
+        last_login_at -> Nullable<Bigint>,
+        last_login_ip -> Nullable<Varchar>,
+        pending_email -> Nullable<Varchar>,
+        email_confirmation_code -> Nullable<Varchar>,
+        totp_secret -> Nullable<Varchar>,
+        totp_enabled -> Bool,
+        google_sub -> Nullable<Varchar>,
+        image_thumb_url -> Nullable<Varchar>,
+        image_medium_url -> Nullable<Varchar>,
+        image_full_url -> Nullable<Varchar>,
+        is_active -> Bool,
+        username -> Nullable<Varchar>,
+        timezone -> Nullable<Varchar>,
+        locale -> Varchar,
+        bio -> Nullable<Text>,
+        website -> Nullable<Varchar>,
+        avatar_url -> Nullable<Varchar>,
+        tenant_id -> Nullable<Integer>,
     }
-}
\ No newline at end of file
+}
+
+table! {
+    refresh_tokens (id) {
+        id -> Nullable<Integer>,
+        user_id -> Integer,
+        token -> Varchar,
+        expires_at -> Bigint,
+        revoked -> Bool,
+    }
+}
+
+table! {
+    login_attempts (id) {
+        id -> Nullable<Integer>,
+        email -> Varchar,
+        ip -> Nullable<Varchar>,
+        attempted_at -> Bigint,
+        tenant_id -> Nullable<Integer>,
+    }
+}
+
+table! {
+    revoked_tokens (jti) {
+        jti -> Varchar,
+        revoked_at -> Bigint,
+        expires_at -> Bigint,
+    }
+}
+
+table! {
+    sessions (id) {
+        id -> Nullable<Integer>,
+        user_id -> Integer,
+        jti -> Varchar,
+        user_agent -> Nullable<Varchar>,
+        ip -> Nullable<Varchar>,
+        created_at -> Bigint,
+        last_seen_at -> Bigint,
+        revoked -> Bool,
+    }
+}
+
+table! {
+    email_log (id) {
+        id -> Nullable<Integer>,
+        user_id -> Nullable<Integer>,
+        template -> Varchar,
+        subject -> Varchar,
+        sent_at -> Bigint,
+        success -> Bool,
+        error_message -> Nullable<Text>,
+    }
+}
+
+table! {
+    backup_codes (id) {
+        id -> Nullable<Integer>,
+        user_id -> Integer,
+        code_hash -> Varchar,
+        used_at -> Nullable<Bigint>,
+    }
+}
+
+table! {
+    phone_otps (id) {
+        id -> Nullable<Integer>,
+        user_id -> Integer,
+        otp_hash -> Varchar,
+        expires_at -> Bigint,
+    }
+}
+
+table! {
+    password_history (id) {
+        id -> Nullable<Integer>,
+        user_id -> Integer,
+        password_hash -> Varchar,
+        created_at -> Bigint,
+    }
+}
+
+table! {
+    invitations (id) {
+        id -> Nullable<Integer>,
+        email -> Varchar,
+        token -> Varchar,
+        invited_by_user_id -> Integer,
+        expires_at -> Bigint,
+        accepted_at -> Nullable<Bigint>,
+        tenant_id -> Nullable<Integer>,
+    }
+}
+
+table! {
+    impersonation_log (id) {
+        id -> Nullable<Integer>,
+        admin_id -> Integer,
+        user_id -> Integer,
+        jti -> Varchar,
+        created_at -> Bigint,
+        expires_at -> Bigint,
+    }
+}
+
+table! {
+    api_keys (id) {
+        id -> Nullable<Integer>,
+        user_id -> Integer,
+        key_prefix -> Varchar,
+        key_hash -> Varchar,
+        name -> Varchar,
+        last_used_at -> Nullable<Bigint>,
+        created_at -> Bigint,
+        revoked -> Bool,
+    }
+}
+
+table! {
+    allowed_origins (id) {
+        id -> Nullable<Integer>,
+        origin -> Varchar,
+    }
+}
+
+table! {
+    webhooks (id) {
+        id -> Nullable<Integer>,
+        url -> Varchar,
+        secret -> Varchar,
+        events -> Text,
+        created_by_user_id -> Integer,
+        active -> Bool,
+        tenant_id -> Nullable<Integer>,
+    }
+}
+
+table! {
+    webhook_deliveries (id) {
+        id -> Nullable<Integer>,
+        webhook_id -> Integer,
+        event -> Varchar,
+        attempt -> Integer,
+        status_code -> Nullable<Integer>,
+        error_message -> Nullable<Text>,
+        delivered_at -> Bigint,
+    }
+}
+
+table! {
+    audit_log (id) {
+        id -> Nullable<Integer>,
+        user_id -> Integer,
+        actor_id -> Nullable<Integer>,
+        action -> Varchar,
+        old_value -> Text,
+        new_value -> Text,
+        ip -> Nullable<Varchar>,
+        created_at -> Bigint,
+    }
+}
+
+table! {
+    oauth_accounts (id) {
+        id -> Nullable<Integer>,
+        user_id -> Integer,
+        provider -> Varchar,
+        provider_sub -> Varchar,
+        access_token_encrypted -> Nullable<Text>,
+        refresh_token_encrypted -> Nullable<Text>,
+        expires_at -> Nullable<Bigint>,
+        created_at -> Bigint,
+    }
+}
+
+table! {
+    notifications (id) {
+        id -> Nullable<Integer>,
+        user_id -> Integer,
+        #[sql_name = "type"]
+        notification_type -> Varchar,
+        payload -> Text,
+        read_at -> Nullable<Bigint>,
+        created_at -> Bigint,
+    }
+}
+
+table! {
+    email_preferences (user_id, email_type) {
+        user_id -> Integer,
+        #[sql_name = "type"]
+        email_type -> Varchar,
+        subscribed -> Bool,
+    }
+}
+
+table! {
+    tenants (id) {
+        id -> Nullable<Integer>,
+        name -> Varchar,
+        slug -> Varchar,
+        created_at -> Bigint,
+    }
+}
+
+table! {
+    feedback (id) {
+        id -> Nullable<Integer>,
+        user_id -> Integer,
+        category -> Varchar,
+        message -> Text,
+        created_at -> Bigint,
+    }
+}
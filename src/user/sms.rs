@@ -0,0 +1,34 @@
+//! Sending of SMS with the REST API of Twilio
+use config::Config;
+use std::time::Duration;
+
+/// Base url of the Twilio REST API
+const TWILIO_API_URL: &str = "https://api.twilio.com/2010-04-01";
+/// Maximum time for a request to Twilio
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Read a required value of the `[sms]` section of the configuration file
+fn setting(config: &Config, key: &str) -> Result<String, String> {
+    config.get_str(&format!("sms.{}", key)).ok()
+        .filter(|value| !value.is_empty())
+        .ok_or_else(|| format!("sms.{} missing", key))
+}
+
+/// Send an SMS with the Twilio account from the `[sms]` section of the configuration file
+pub fn send(to: &str, body: &str, config: &Config) -> Result<(), String> {
+    let account_sid = setting(config, "account_sid")?;
+    let auth_token = setting(config, "auth_token")?;
+    let from = setting(config, "from")?;
+    let client = reqwest::blocking::Client::builder()
+        .timeout(REQUEST_TIMEOUT)
+        .build()
+        .map_err(|e| format!("HTTP client could not be created: {}", e))?;
+    client
+        .post(&format!("{}/Accounts/{}/Messages.json", TWILIO_API_URL, account_sid))
+        .basic_auth(&account_sid, Some(&auth_token))
+        .form(&[("To", to), ("From", from.as_str()), ("Body", body)])
+        .send()
+        .and_then(|response| response.error_for_status())
+        .map(|_| ())
+        .map_err(|e| format!("Twilio request failed: {}", e))
+}
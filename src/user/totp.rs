@@ -0,0 +1,75 @@
+//! Time based one time passwords for the two factor authentication
+use crypto::aead::{AeadDecryptor, AeadEncryptor};
+use crypto::aes::KeySize;
+use crypto::aes_gcm::AesGcm;
+use config::Config;
+use rand::Rng;
+use sha2::{Digest, Sha256};
+use std::time::SystemTime;
+use totp_rs::{Algorithm, TOTP};
+
+/// Number of random bytes of a TOTP secret
+const SECRET_LENGTH: usize = 20;
+/// Issuer that is shown in authenticator apps if `totp.issuer` is not configured
+const DEFAULT_ISSUER: &str = "webapp_boilerplate";
+
+/// Derive the encryption key for TOTP secrets from the configured secret key
+fn encryption_key(config: &Config) -> Result<Vec<u8>, String> {
+    let secretkey = config.get_str("secretkey").map_err(|_| "Secret key missing".to_string())?;
+    Ok(Sha256::digest(secretkey.as_bytes()).to_vec())
+}
+
+/// Create a new random TOTP secret
+pub fn generate_secret() -> Vec<u8> {
+    let mut secret = vec![0u8; SECRET_LENGTH];
+    rand::thread_rng().fill(&mut secret[..]);
+    secret
+}
+
+/// Encrypt a TOTP secret with AES-256-GCM for storing it in the database
+pub fn encrypt_secret(secret: &[u8], config: &Config) -> Result<String, String> {
+    let key = encryption_key(config)?;
+    let mut nonce = [0u8; 12];
+    rand::thread_rng().fill(&mut nonce[..]);
+    let mut ciphertext = vec![0u8; secret.len()];
+    let mut tag = [0u8; 16];
+    AesGcm::new(KeySize::KeySize256, &key, &nonce, &[]).encrypt(secret, &mut ciphertext, &mut tag);
+    // nonce and tag are stored in front of the ciphertext
+    let mut stored = nonce.to_vec();
+    stored.extend_from_slice(&tag);
+    stored.extend_from_slice(&ciphertext);
+    Ok(base64::encode(&stored))
+}
+
+/// Decrypt a TOTP secret that was stored in the database
+pub fn decrypt_secret(stored: &str, config: &Config) -> Result<Vec<u8>, String> {
+    let key = encryption_key(config)?;
+    let stored = base64::decode(stored).map_err(|_| "TOTP secret not valid".to_string())?;
+    if stored.len() < 28 {
+        return Err("TOTP secret not valid".to_string());
+    }
+    let (nonce, rest) = stored.split_at(12);
+    let (tag, ciphertext) = rest.split_at(16);
+    let mut secret = vec![0u8; ciphertext.len()];
+    if AesGcm::new(KeySize::KeySize256, &key, nonce, &[]).decrypt(ciphertext, &mut secret, tag) {
+        Ok(secret)
+    } else {
+        Err("TOTP secret could not be decrypted".to_string())
+    }
+}
+
+/// Check a code of the authenticator app against the secret. Codes of the previous and next period are accepted as well
+pub fn check_code(secret: &[u8], code: &str) -> bool {
+    // issuer and account name are only part of the provisioning URL
+    let totp = TOTP::new_unchecked(Algorithm::SHA1, 6, 1, 30, secret.to_vec(), None, String::new());
+    totp.check(code.trim(), SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).unwrap().as_secs())
+}
+
+/// Create the provisioning URL and a QR code of it as PNG data URI for setting up an authenticator app
+pub fn provisioning(secret: &[u8], email: &str, config: &Config) -> Result<(String, String), String> {
+    let issuer = config.get_str("totp.issuer").unwrap_or_else(|_| DEFAULT_ISSUER.to_string());
+    let totp = TOTP::new(Algorithm::SHA1, 6, 1, 30, secret.to_vec(), Some(issuer), email.to_string()).map_err(|e| e.to_string())?;
+    let url = totp.get_url();
+    let qr_code = totp.get_qr_base64()?;
+    Ok((url, format!("data:image/png;base64,{}", qr_code)))
+}
\ No newline at end of file
@@ -0,0 +1,31 @@
+use regex::Regex;
+
+/// Requirements for usernames. Read once at startup and stored in Rocket's managed state
+#[derive(Debug)]
+pub struct UsernamePolicy {
+    /// Pattern that the whole username has to match
+    pub pattern: Regex,
+}
+
+/// Letters, digits and underscores with 3 to 32 characters if `username.pattern` is not configured
+const DEFAULT_PATTERN: &str = "^[A-Za-z0-9_]{3,32}$";
+/// Length of the `username` column. Longer usernames are rejected even if the pattern allows them
+const MAX_LENGTH: usize = 32;
+
+impl UsernamePolicy {
+    /// Read `username.pattern` from the configuration. Panics on an invalid regular expression
+    pub fn from_config() -> UsernamePolicy {
+        let pattern = crate::load_config().ok()
+            .and_then(|c| c.get_str("username.pattern").ok())
+            .unwrap_or_else(|| DEFAULT_PATTERN.to_string());
+        match Regex::new(&pattern) {
+            Ok(pattern) => UsernamePolicy { pattern },
+            Err(e) => panic!("username.pattern is not a valid regular expression: {}", e)
+        }
+    }
+
+    /// Check whether a username matches the pattern and fits into the database column
+    pub fn is_valid(&self, username: &str) -> bool {
+        username.chars().count() <= MAX_LENGTH && self.pattern.is_match(username)
+    }
+}
@@ -0,0 +1,320 @@
+//! Test client with an in-memory SQLite database and helpers for the common requests
+#![allow(dead_code)]
+use std::collections::HashMap;
+use std::env;
+use std::sync::Once;
+
+use diesel::prelude::*;
+use diesel::SqliteConnection;
+use rocket::config::{Config, Environment, Value};
+use rocket::http::{ContentType, Header, Status};
+use rocket::local::Client;
+use serde_json::Value as JsonValue;
+use webapp_boilerplate::mailer::{MailQueue, SentEmail};
+
+/// Schema of the tables as in the MySQL migrations. Applied to every new test database
+const SCHEMA: &str = "
+CREATE TABLE tenants (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    name VARCHAR(255) NOT NULL,
+    slug VARCHAR(63) NOT NULL UNIQUE,
+    created_at BIGINT NOT NULL
+);
+CREATE TABLE users (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    firstname VARCHAR(255) NOT NULL DEFAULT '',
+    email VARCHAR(255) NOT NULL,
+    password VARCHAR(255) NOT NULL,
+    registration_code VARCHAR(255),
+    reset_code VARCHAR(255),
+    image BLOB,
+    create_date BIGINT NOT NULL,
+    edit_date BIGINT NOT NULL,
+    role VARCHAR(255) NOT NULL DEFAULT 'user',
+    deleted_at BIGINT,
+    reset_code_expires_at BIGINT,
+    registration_code_expires_at BIGINT,
+    lastname VARCHAR(255),
+    phone VARCHAR(255),
+    phone_verified BOOLEAN NOT NULL DEFAULT 0,
+    last_login_at BIGINT,
+    last_login_ip VARCHAR(255),
+    pending_email VARCHAR(255),
+    email_confirmation_code VARCHAR(255),
+    totp_secret VARCHAR(255),
+    totp_enabled BOOLEAN NOT NULL DEFAULT 0,
+    google_sub VARCHAR(255),
+    image_thumb_url VARCHAR(255),
+    image_medium_url VARCHAR(255),
+    image_full_url VARCHAR(255),
+    is_active BOOLEAN NOT NULL DEFAULT 1,
+    username VARCHAR(32) UNIQUE,
+    timezone VARCHAR(64),
+    locale VARCHAR(16) NOT NULL DEFAULT 'en',
+    bio TEXT,
+    website VARCHAR(2048),
+    avatar_url VARCHAR(2048),
+    tenant_id INTEGER REFERENCES tenants (id)
+);
+CREATE UNIQUE INDEX users_email_unique ON users (COALESCE(tenant_id, 0), email);
+CREATE TABLE refresh_tokens (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    user_id INTEGER NOT NULL,
+    token VARCHAR(255) NOT NULL,
+    expires_at BIGINT NOT NULL,
+    revoked BOOLEAN NOT NULL DEFAULT 0
+);
+CREATE TABLE login_attempts (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    email VARCHAR(255) NOT NULL,
+    ip VARCHAR(255),
+    attempted_at BIGINT NOT NULL,
+    tenant_id INTEGER
+);
+CREATE TABLE revoked_tokens (
+    jti VARCHAR(255) PRIMARY KEY,
+    revoked_at BIGINT NOT NULL,
+    expires_at BIGINT NOT NULL
+);
+CREATE TABLE sessions (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    user_id INTEGER NOT NULL,
+    jti VARCHAR(255) NOT NULL,
+    user_agent VARCHAR(255),
+    ip VARCHAR(255),
+    created_at BIGINT NOT NULL,
+    last_seen_at BIGINT NOT NULL,
+    revoked BOOLEAN NOT NULL DEFAULT 0
+);
+CREATE TABLE email_log (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    user_id INTEGER,
+    template VARCHAR(255) NOT NULL,
+    subject VARCHAR(255) NOT NULL,
+    sent_at BIGINT NOT NULL,
+    success BOOLEAN NOT NULL,
+    error_message TEXT
+);
+CREATE TABLE backup_codes (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    user_id INTEGER NOT NULL,
+    code_hash VARCHAR(255) NOT NULL,
+    used_at BIGINT
+);
+CREATE TABLE phone_otps (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    user_id INTEGER NOT NULL,
+    otp_hash VARCHAR(255) NOT NULL,
+    expires_at BIGINT NOT NULL
+);
+CREATE TABLE password_history (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    user_id INTEGER NOT NULL,
+    password_hash VARCHAR(255) NOT NULL,
+    created_at BIGINT NOT NULL
+);
+CREATE TABLE invitations (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    email VARCHAR(255) NOT NULL,
+    token VARCHAR(255) NOT NULL,
+    invited_by_user_id INTEGER NOT NULL,
+    expires_at BIGINT NOT NULL,
+    accepted_at BIGINT,
+    tenant_id INTEGER
+);
+CREATE TABLE impersonation_log (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    admin_id INTEGER NOT NULL,
+    user_id INTEGER NOT NULL,
+    jti VARCHAR(255) NOT NULL,
+    created_at BIGINT NOT NULL,
+    expires_at BIGINT NOT NULL
+);
+CREATE TABLE api_keys (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    user_id INTEGER NOT NULL,
+    key_prefix VARCHAR(255) NOT NULL,
+    key_hash VARCHAR(255) NOT NULL,
+    name VARCHAR(255) NOT NULL,
+    last_used_at BIGINT,
+    created_at BIGINT NOT NULL,
+    revoked BOOLEAN NOT NULL DEFAULT 0
+);
+CREATE TABLE allowed_origins (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    origin VARCHAR(255) NOT NULL
+);
+CREATE TABLE webhooks (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    url VARCHAR(255) NOT NULL,
+    secret VARCHAR(255) NOT NULL,
+    events TEXT NOT NULL,
+    created_by_user_id INTEGER NOT NULL,
+    active BOOLEAN NOT NULL DEFAULT 1,
+    tenant_id INTEGER
+);
+CREATE TABLE webhook_deliveries (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    webhook_id INTEGER NOT NULL,
+    event VARCHAR(255) NOT NULL,
+    attempt INTEGER NOT NULL,
+    status_code INTEGER,
+    error_message TEXT,
+    delivered_at BIGINT NOT NULL
+);
+CREATE TABLE audit_log (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    user_id INTEGER NOT NULL,
+    actor_id INTEGER,
+    action VARCHAR(255) NOT NULL,
+    old_value TEXT NOT NULL,
+    new_value TEXT NOT NULL,
+    ip VARCHAR(255),
+    created_at BIGINT NOT NULL
+);
+CREATE TABLE oauth_accounts (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    user_id INTEGER NOT NULL,
+    provider VARCHAR(32) NOT NULL,
+    provider_sub VARCHAR(255) NOT NULL,
+    access_token_encrypted TEXT,
+    refresh_token_encrypted TEXT,
+    expires_at BIGINT,
+    created_at BIGINT NOT NULL,
+    UNIQUE (provider, provider_sub)
+);
+CREATE TABLE notifications (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    user_id INTEGER NOT NULL,
+    type VARCHAR(64) NOT NULL,
+    payload TEXT NOT NULL,
+    read_at BIGINT,
+    created_at BIGINT NOT NULL
+);
+CREATE TABLE feedback (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    user_id INTEGER NOT NULL,
+    category VARCHAR(16) NOT NULL,
+    message TEXT NOT NULL,
+    created_at BIGINT NOT NULL
+);
+CREATE TABLE email_preferences (
+    user_id INTEGER NOT NULL,
+    type VARCHAR(64) NOT NULL,
+    subscribed BOOLEAN NOT NULL DEFAULT 1,
+    PRIMARY KEY (user_id, type)
+);
+";
+
+// the columns of the users table that the tests read directly. The codes are only sent by mail
+table! {
+    users (id) {
+        id -> Nullable<Integer>,
+        email -> Varchar,
+        registration_code -> Nullable<Varchar>,
+        reset_code -> Nullable<Varchar>,
+        pending_email -> Nullable<Varchar>,
+    }
+}
+
+static CONFIGURE: Once = Once::new();
+
+/// Configure the application with environment variables, because `Config.toml` is not available in tests
+fn configure() {
+    CONFIGURE.call_once(|| {
+        env::set_var("APP_SECRETKEY", "integration-test-secret-key");
+        // the lowest bcrypt cost keeps the tests fast
+        env::set_var("APP_SECURITY__BCRYPT_COST", "4");
+        // mails are captured by the MockMailer instead of being sent
+        env::set_var("APP_EMAIL__BACKEND", "mock");
+    });
+}
+
+/// A test client with its own database
+pub struct TestApp {
+    pub client: Client,
+    /// Keeps the in-memory database alive and is used to read the codes that are normally sent by mail
+    pub connection: SqliteConnection,
+}
+
+/// Build a test client with a new in-memory database
+pub fn test_app() -> TestApp {
+    configure();
+    // a named in-memory database with a shared cache is seen by all connections of the pool
+    let url = format!("file:test_{}?mode=memory&cache=shared", uuid::Uuid::new_v4().to_simple());
+    let connection = SqliteConnection::establish(&url).expect("test database");
+    connection.batch_execute(SCHEMA).expect("schema");
+
+    let mut database = HashMap::new();
+    database.insert("url", Value::from(url));
+    // the webhook queue keeps one connection
+    database.insert("pool_size", Value::from(4));
+    let mut databases = HashMap::new();
+    databases.insert("webapp_boilerplate", Value::from(database));
+    let config = Config::build(Environment::Development)
+        .extra("databases", databases)
+        .finalize()
+        .expect("rocket config");
+    let client = Client::new(webapp_boilerplate::build(rocket::custom(config))).expect("valid rocket instance");
+    TestApp { client, connection }
+}
+
+/// The mails that were sent by the application so far
+pub fn sent_emails(client: &Client) -> Vec<SentEmail> {
+    client.rocket().state::<MailQueue>().and_then(MailQueue::mock_mailer).expect("mock mailer").sent_emails()
+}
+
+/// Parse the JSON body of a response
+pub fn json_body(body: Option<String>) -> JsonValue {
+    serde_json::from_str(&body.expect("response body")).expect("JSON body")
+}
+
+/// Register a user and return the response body
+pub fn register_user(client: &Client, email: &str, password: &str) -> JsonValue {
+    let mut response = client.post("/user/")
+        .header(ContentType::JSON)
+        .body(serde_json::json!({"email": email, "password": password}).to_string())
+        .dispatch();
+    assert_eq!(response.status(), Status::Ok);
+    json_body(response.body_string())
+}
+
+/// Activate a user with the registration code and return the token
+pub fn activate_user(client: &Client, code: &str) -> String {
+    let mut response = client.get(format!("/user/activate/{}", code)).dispatch();
+    assert_eq!(response.status(), Status::Ok);
+    json_body(response.body_string())["data"]["token"].as_str().expect("token").to_string()
+}
+
+/// Log in and return the token
+pub fn login_user(client: &Client, email: &str, password: &str) -> String {
+    let mut response = client.post("/user/login")
+        .header(ContentType::JSON)
+        .body(serde_json::json!({"email": email, "password": password}).to_string())
+        .dispatch();
+    assert_eq!(response.status(), Status::Ok);
+    json_body(response.body_string())["data"]["token"].as_str().expect("token").to_string()
+}
+
+/// Authorization header with a Bearer token
+pub fn bearer(token: &str) -> Header<'static> {
+    Header::new("Authorization", format!("Bearer {}", token))
+}
+
+/// The registration code of a user, which is normally sent by mail
+pub fn registration_code(app: &TestApp, email: &str) -> String {
+    users::table.filter(users::email.eq(email)).select(users::registration_code).first::<Option<String>>(&app.connection)
+        .expect("user").expect("registration code")
+}
+
+/// The reset code of a user, which is normally sent by mail
+pub fn reset_code(app: &TestApp, email: &str) -> String {
+    users::table.filter(users::email.eq(email)).select(users::reset_code).first::<Option<String>>(&app.connection)
+        .expect("user").expect("reset code")
+}
+
+/// The new email address of a user that waits for its confirmation
+pub fn pending_email(app: &TestApp, email: &str) -> Option<String> {
+    users::table.filter(users::email.eq(email)).select(users::pending_email).first::<Option<String>>(&app.connection)
+        .expect("user")
+}
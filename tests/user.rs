@@ -0,0 +1,221 @@
+//! Integration tests of the user routes. Only compiled with the `sqlite` feature:
+//! `cargo test --no-default-features --features sqlite`
+#![cfg(feature = "sqlite")]
+#[macro_use]
+extern crate diesel;
+
+mod common;
+
+use diesel::RunQueryDsl;
+use rocket::http::{ContentType, Header, Status};
+
+use common::{activate_user, bearer, json_body, login_user, pending_email, register_user, registration_code, reset_code, sent_emails, test_app};
+
+const EMAIL: &str = "info@example.com";
+const PASSWORD: &str = "example_password";
+
+#[test]
+fn create_user() {
+    let app = test_app();
+    let body = register_user(&app.client, EMAIL, PASSWORD);
+    assert_eq!(body["data"]["user"]["email"], EMAIL);
+    assert_eq!(body["data"]["user"]["is_confirmed"], false);
+    // the activation mail contains the registration code
+    let emails = sent_emails(&app.client);
+    assert_eq!(emails.len(), 1);
+    assert_eq!(emails[0].to, EMAIL);
+    assert_eq!(emails[0].template, "createUser");
+    assert_eq!(emails[0].context["registration_code"], registration_code(&app, EMAIL));
+    // the same email address can not be registered twice
+    let response = app.client.post("/user/")
+        .header(ContentType::JSON)
+        .body(serde_json::json!({"email": EMAIL, "password": PASSWORD}).to_string())
+        .dispatch();
+    assert_eq!(response.status(), Status::Conflict);
+}
+
+#[test]
+fn create_user_with_email_of_deleted_user() {
+    let app = test_app();
+    register_user(&app.client, EMAIL, PASSWORD);
+    // soft deleted users keep their email address until the grace period is over
+    diesel::sql_query("UPDATE users SET deleted_at = 1").execute(&app.connection).expect("soft delete");
+    let response = app.client.post("/user/")
+        .header(ContentType::JSON)
+        .body(serde_json::json!({"email": EMAIL, "password": PASSWORD}).to_string())
+        .dispatch();
+    assert_eq!(response.status(), Status::Conflict);
+}
+
+#[test]
+fn activate() {
+    let app = test_app();
+    register_user(&app.client, EMAIL, PASSWORD);
+    let token = activate_user(&app.client, &registration_code(&app, EMAIL));
+    let mut response = app.client.get("/user/me").header(bearer(&token)).dispatch();
+    assert_eq!(response.status(), Status::Ok);
+    assert_eq!(json_body(response.body_string())["data"]["is_confirmed"], true);
+}
+
+#[test]
+fn login_requires_activation() {
+    let app = test_app();
+    register_user(&app.client, EMAIL, PASSWORD);
+    let response = app.client.post("/user/login")
+        .header(ContentType::JSON)
+        .body(serde_json::json!({"email": EMAIL, "password": PASSWORD}).to_string())
+        .dispatch();
+    assert_eq!(response.status(), Status::Ok);
+    let body = json_body(response.body_string());
+    assert_eq!(body["data"]["is_confirmed"], false);
+    assert_eq!(body["status"]["error_code"], "ACCOUNT_NOT_ACTIVATED");
+    assert!(body["data"]["token"].is_null());
+}
+
+#[test]
+fn login() {
+    let app = test_app();
+    register_user(&app.client, EMAIL, PASSWORD);
+    activate_user(&app.client, &registration_code(&app, EMAIL));
+    let token = login_user(&app.client, EMAIL, PASSWORD);
+    assert_eq!(app.client.get("/user/me").header(bearer(&token)).dispatch().status(), Status::Ok);
+    // a wrong password is rejected
+    let response = app.client.post("/user/login")
+        .header(ContentType::JSON)
+        .body(serde_json::json!({"email": EMAIL, "password": "wrong_password"}).to_string())
+        .dispatch();
+    assert_eq!(response.status(), Status::Unauthorized);
+}
+
+#[test]
+fn update_user() {
+    let app = test_app();
+    register_user(&app.client, EMAIL, PASSWORD);
+    let token = activate_user(&app.client, &registration_code(&app, EMAIL));
+    let mut response = app.client.put("/user/")
+        .header(ContentType::JSON)
+        .header(bearer(&token))
+        .body(serde_json::json!({"firstname": "Daniel", "lastname": "Smith", "phone": "+49 123 456789"}).to_string())
+        .dispatch();
+    assert_eq!(response.status(), Status::Ok);
+    // the response contains the updated user
+    assert_eq!(json_body(response.body_string())["data"]["user"]["firstname"], "Daniel");
+    let user = json_body(app.client.get("/user/me").header(bearer(&token)).dispatch().body_string())["data"].clone();
+    assert_eq!(user["firstname"], "Daniel");
+    assert_eq!(user["lastname"], "Smith");
+    assert_eq!(user["phone"], "+49 123 456789");
+}
+
+#[test]
+fn patch_bio_and_website() {
+    let app = test_app();
+    register_user(&app.client, EMAIL, PASSWORD);
+    let token = activate_user(&app.client, &registration_code(&app, EMAIL));
+    let mut response = app.client.patch("/user/")
+        .header(ContentType::JSON)
+        .header(bearer(&token))
+        .body(serde_json::json!({"bio": "Backend developer", "website": "https://example.com"}).to_string())
+        .dispatch();
+    assert_eq!(response.status(), Status::Ok);
+    let user = json_body(response.body_string())["data"]["user"].clone();
+    assert_eq!(user["bio"], "Backend developer");
+    assert_eq!(user["website"], "https://example.com");
+    // websites without http or https and too long bios are rejected
+    for body in &[serde_json::json!({"website": "ftp://example.com"}), serde_json::json!({"bio": "x".repeat(501)})] {
+        let response = app.client.patch("/user/")
+            .header(ContentType::JSON)
+            .header(bearer(&token))
+            .body(body.to_string())
+            .dispatch();
+        assert_eq!(response.status(), Status::UnprocessableEntity);
+    }
+}
+
+#[test]
+fn reset_password() {
+    let app = test_app();
+    register_user(&app.client, EMAIL, PASSWORD);
+    activate_user(&app.client, &registration_code(&app, EMAIL));
+    let response = app.client.post("/user/request_reset")
+        .header(ContentType::JSON)
+        .body(serde_json::json!({"email": EMAIL}).to_string())
+        .dispatch();
+    assert_eq!(response.status(), Status::Ok);
+    let email = sent_emails(&app.client).pop().expect("reset mail");
+    assert!(email.context_keys.contains(&"reset_code".to_string()));
+    let mut response = app.client.post("/user/reset_password")
+        .header(ContentType::JSON)
+        .body(serde_json::json!({"reset_code": reset_code(&app, EMAIL), "password": "new_example_password"}).to_string())
+        .dispatch();
+    assert_eq!(response.status(), Status::Ok);
+    assert!(json_body(response.body_string())["data"]["token"].is_string());
+    // only the new password is accepted
+    login_user(&app.client, EMAIL, "new_example_password");
+    let response = app.client.post("/user/login")
+        .header(ContentType::JSON)
+        .body(serde_json::json!({"email": EMAIL, "password": PASSWORD}).to_string())
+        .dispatch();
+    assert_eq!(response.status(), Status::Unauthorized);
+}
+
+#[test]
+fn update_email_requires_password() {
+    let app = test_app();
+    register_user(&app.client, EMAIL, PASSWORD);
+    let token = activate_user(&app.client, &registration_code(&app, EMAIL));
+    let response = app.client.put("/user/email")
+        .header(ContentType::JSON)
+        .header(bearer(&token))
+        .body(serde_json::json!({"email": "updated@example.com", "password": "wrong_password"}).to_string())
+        .dispatch();
+    assert_eq!(response.status(), Status::Unauthorized);
+    assert_eq!(pending_email(&app, EMAIL), None);
+    // with the correct password the new address waits for its confirmation
+    let response = app.client.put("/user/email")
+        .header(ContentType::JSON)
+        .header(bearer(&token))
+        .body(serde_json::json!({"email": "updated@example.com", "password": PASSWORD}).to_string())
+        .dispatch();
+    assert_eq!(response.status(), Status::Ok);
+    assert_eq!(pending_email(&app, EMAIL), Some(String::from("updated@example.com")));
+}
+
+#[test]
+fn same_email_in_two_tenants() {
+    let app = test_app();
+    diesel::sql_query("INSERT INTO tenants (name, slug, created_at) VALUES ('ACME Inc.', 'acme', 0)")
+        .execute(&app.connection)
+        .expect("tenant");
+    register_user(&app.client, EMAIL, PASSWORD);
+    // the host acme.example.com belongs to the tenant, so the email address is still free there
+    for expected in &[Status::Ok, Status::Conflict] {
+        let response = app.client.post("/user/")
+            .header(ContentType::JSON)
+            .header(Header::new("Host", "acme.example.com"))
+            .body(serde_json::json!({"email": EMAIL, "password": PASSWORD}).to_string())
+            .dispatch();
+        assert_eq!(response.status(), *expected);
+    }
+}
+
+#[test]
+fn send_feedback() {
+    let app = test_app();
+    register_user(&app.client, EMAIL, PASSWORD);
+    let token = activate_user(&app.client, &registration_code(&app, EMAIL));
+    let response = app.client.post("/user/feedback")
+        .header(ContentType::JSON)
+        .header(bearer(&token))
+        .body(serde_json::json!({"category": "feature", "message": "Please add a dark mode"}).to_string())
+        .dispatch();
+    assert_eq!(response.status(), Status::Ok);
+    // unknown categories and too long messages are rejected
+    for body in &[serde_json::json!({"category": "praise", "message": "Great"}), serde_json::json!({"category": "bug", "message": "x".repeat(2001)})] {
+        let response = app.client.post("/user/feedback")
+            .header(ContentType::JSON)
+            .header(bearer(&token))
+            .body(body.to_string())
+            .dispatch();
+        assert_eq!(response.status(), Status::UnprocessableEntity);
+    }
+}